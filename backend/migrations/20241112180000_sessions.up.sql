@@ -0,0 +1,14 @@
+-- Create sessions table
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Create index on sessions.user_id for faster retrieval
+CREATE INDEX idx_sessions_user_id ON sessions(user_id);
+
+-- Create index on sessions.expires_at for sweeping expired sessions
+CREATE INDEX idx_sessions_expires_at ON sessions(expires_at);
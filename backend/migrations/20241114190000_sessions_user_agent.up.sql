@@ -0,0 +1,2 @@
+-- Remember which client a session was created from
+ALTER TABLE sessions ADD COLUMN user_agent TEXT;
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN salt VARCHAR(255) NOT NULL DEFAULT '';
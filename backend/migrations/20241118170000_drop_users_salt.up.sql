@@ -0,0 +1,2 @@
+-- The salt is part of the PHC string stored in password_hash
+ALTER TABLE users DROP COLUMN salt;
@@ -0,0 +1,26 @@
+-- TOTP secret of the user, only used for login once totp_enabled is set
+ALTER TABLE users ADD COLUMN totp_secret VARCHAR(255);
+ALTER TABLE users ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- One-time codes for logging in without the authenticator
+CREATE TABLE two_factor_recovery_codes (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    code_hash VARCHAR(255) NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_two_factor_recovery_codes_user_id ON two_factor_recovery_codes(user_id);
+
+-- Logins which passed the password check and wait for the second factor
+CREATE TABLE pending_logins (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    attempts INTEGER DEFAULT 0 NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_pending_logins_expires_at ON pending_logins(expires_at);
@@ -0,0 +1,7 @@
+-- Failed login attempts per username and per client address
+CREATE TABLE login_failures (
+    subject VARCHAR(512) PRIMARY KEY,
+    failures INTEGER NOT NULL,
+    last_failure_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    locked_until TIMESTAMP WITH TIME ZONE NOT NULL
+);
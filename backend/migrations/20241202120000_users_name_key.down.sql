@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_users_name_key;
+
+ALTER TABLE users DROP COLUMN IF EXISTS name_key;
@@ -0,0 +1,5 @@
+-- Case-folded confusable skeleton of the name, keeps look-alike names unique.
+-- Existing rows are filled in by the backend on startup.
+ALTER TABLE users ADD COLUMN name_key VARCHAR(255);
+
+CREATE UNIQUE INDEX idx_users_name_key ON users(name_key);
@@ -0,0 +1,6 @@
+-- Role of the user, moderators and admins are "staff" and may moderate content of others
+ALTER TABLE users ADD COLUMN role VARCHAR(16) NOT NULL DEFAULT 'user'
+    CHECK (role IN ('user', 'moderator', 'admin'));
+
+-- Staff is a handful of users, only they need to be looked up by role
+CREATE INDEX idx_users_role ON users(role) WHERE role <> 'user';
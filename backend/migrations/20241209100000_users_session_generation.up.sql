@@ -0,0 +1,3 @@
+-- Bumped to revoke all sessions of a user, signed session cookies carry the
+-- generation they were issued for
+ALTER TABLE users ADD COLUMN session_generation INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,11 @@
+-- One-time codes for setting a new password, handed out at registration since
+-- there is no email address to send a reset link to
+CREATE TABLE account_recovery_codes (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    code_hash VARCHAR(255) NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_account_recovery_codes_user_id ON account_recovery_codes(user_id);
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS idx_users_invite_id;
+DROP INDEX IF EXISTS idx_users_invited_by;
+
+ALTER TABLE users DROP COLUMN IF EXISTS invite_id;
+ALTER TABLE users DROP COLUMN IF EXISTS invited_by;
+
+DROP TABLE IF EXISTS invites;
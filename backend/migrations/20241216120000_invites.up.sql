@@ -0,0 +1,21 @@
+-- Invite codes for instances which do not allow open registration
+CREATE TABLE invites (
+    id UUID PRIMARY KEY,
+    code_hash VARCHAR(255) NOT NULL UNIQUE,
+    created_by UUID,
+    -- NULL means unlimited
+    max_uses INTEGER,
+    uses INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_invites_created_by ON invites(created_by);
+
+-- Who brought a user in, kept even after the invite itself is gone
+ALTER TABLE users ADD COLUMN invited_by UUID REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE users ADD COLUMN invite_id UUID REFERENCES invites(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_users_invited_by ON users(invited_by);
+CREATE INDEX idx_users_invite_id ON users(invite_id);
@@ -0,0 +1,3 @@
+ALTER TABLE posts DROP COLUMN IF EXISTS poster_id;
+
+ALTER TABLE threads DROP COLUMN IF EXISTS poster_secret;
@@ -0,0 +1,7 @@
+-- Key for the pseudonymous poster ids of a thread, never leaves the database.
+-- Volatile defaults are evaluated per row, so existing threads get their own secret.
+ALTER TABLE threads ADD COLUMN poster_secret VARCHAR(64) NOT NULL
+    DEFAULT replace(gen_random_uuid()::text || gen_random_uuid()::text, '-', '');
+
+-- Short id of whoever wrote the post, stable within its thread
+ALTER TABLE posts ADD COLUMN poster_id VARCHAR(16);
@@ -0,0 +1,3 @@
+-- Name and tripcode of anonymous posts, the tripcode secret itself is never stored
+ALTER TABLE posts ADD COLUMN display_name VARCHAR(64);
+ALTER TABLE posts ADD COLUMN tripcode VARCHAR(16);
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_threads_author_id;
+
+ALTER TABLE threads DROP COLUMN IF EXISTS description;
+ALTER TABLE threads DROP COLUMN IF EXISTS author_id;
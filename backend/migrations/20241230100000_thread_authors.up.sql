@@ -0,0 +1,6 @@
+-- Who opened a thread, NULL for anonymous threads and deleted users
+ALTER TABLE threads ADD COLUMN author_id UUID REFERENCES users(id) ON DELETE SET NULL;
+-- Optional short text shown with the thread name
+ALTER TABLE threads ADD COLUMN description TEXT;
+
+CREATE INDEX idx_threads_author_id ON threads(author_id);
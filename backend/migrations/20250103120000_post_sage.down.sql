@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_threads_last_active;
+
+ALTER TABLE posts DROP COLUMN IF EXISTS sage;
@@ -0,0 +1,5 @@
+-- Posts which asked not to bump their thread
+ALTER TABLE posts ADD COLUMN sage BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Thread listing sorted by bump order
+CREATE INDEX idx_threads_last_active ON threads(last_active);
@@ -0,0 +1,2 @@
+ALTER TABLE threads DROP COLUMN IF EXISTS locked;
+ALTER TABLE threads DROP COLUMN IF EXISTS pinned;
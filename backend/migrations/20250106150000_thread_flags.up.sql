@@ -0,0 +1,3 @@
+-- Pinned threads are listed before all others, locked threads take no new posts
+ALTER TABLE threads ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE threads ADD COLUMN locked BOOLEAN NOT NULL DEFAULT FALSE;
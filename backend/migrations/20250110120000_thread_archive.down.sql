@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_threads_archived_at;
+
+ALTER TABLE threads DROP COLUMN IF EXISTS archived_at;
@@ -0,0 +1,4 @@
+-- Archived threads are read-only and only listed on request
+ALTER TABLE threads ADD COLUMN archived_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_threads_archived_at ON threads(archived_at);
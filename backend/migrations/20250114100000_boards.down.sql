@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_threads_board_id;
+
+ALTER TABLE threads DROP COLUMN IF EXISTS board_id;
+
+DROP TABLE IF EXISTS boards;
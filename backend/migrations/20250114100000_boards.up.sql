@@ -0,0 +1,18 @@
+-- Boards group threads by topic, each with its own posting rules
+CREATE TABLE IF NOT EXISTS boards (
+    id UUID PRIMARY KEY NOT NULL,
+    slug VARCHAR(32) NOT NULL UNIQUE,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    allow_anonymous BOOLEAN NOT NULL DEFAULT TRUE,
+    -- NULL leaves the length of posts unlimited
+    max_post_length INTEGER CHECK (max_post_length > 0),
+    -- Lowest role allowed to open threads, NULL lets anyone do it
+    min_thread_role VARCHAR(16) CHECK (min_thread_role IN ('user', 'moderator', 'admin')),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- Threads created before boards existed stay without one
+ALTER TABLE threads ADD COLUMN board_id UUID REFERENCES boards(id);
+
+CREATE INDEX idx_threads_board_id ON threads(board_id);
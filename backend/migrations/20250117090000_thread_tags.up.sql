@@ -0,0 +1,15 @@
+-- Free-form tags, shared by all threads using the same name
+CREATE TABLE IF NOT EXISTS tags (
+    id UUID PRIMARY KEY NOT NULL,
+    name VARCHAR(32) NOT NULL UNIQUE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS thread_tags (
+    thread_id UUID NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+    tag_id UUID NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (thread_id, tag_id)
+);
+
+-- Filtering looks up the threads of a tag, the primary key covers the other direction
+CREATE INDEX idx_thread_tags_tag_id ON thread_tags(tag_id);
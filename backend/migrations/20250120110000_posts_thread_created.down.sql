@@ -0,0 +1 @@
+DROP INDEX IF EXISTS idx_posts_thread_id_created_at;
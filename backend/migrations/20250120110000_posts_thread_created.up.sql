@@ -0,0 +1,2 @@
+-- Thread statistics look up the first and last post of each listed thread
+CREATE INDEX idx_posts_thread_id_created_at ON posts(thread_id, created_at);
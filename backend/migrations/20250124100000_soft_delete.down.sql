@@ -0,0 +1,10 @@
+DROP INDEX IF EXISTS idx_posts_deleted_at;
+DROP INDEX IF EXISTS idx_threads_deleted_at;
+
+ALTER TABLE posts DROP COLUMN IF EXISTS delete_reason;
+ALTER TABLE posts DROP COLUMN IF EXISTS deleted_by;
+ALTER TABLE posts DROP COLUMN IF EXISTS deleted_at;
+
+ALTER TABLE threads DROP COLUMN IF EXISTS delete_reason;
+ALTER TABLE threads DROP COLUMN IF EXISTS deleted_by;
+ALTER TABLE threads DROP COLUMN IF EXISTS deleted_at;
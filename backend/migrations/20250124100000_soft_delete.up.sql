@@ -0,0 +1,11 @@
+-- Deleted threads and posts are kept as tombstones until they are purged
+ALTER TABLE threads ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE threads ADD COLUMN deleted_by UUID REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE threads ADD COLUMN delete_reason TEXT;
+
+ALTER TABLE posts ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE posts ADD COLUMN deleted_by UUID REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE posts ADD COLUMN delete_reason TEXT;
+
+CREATE INDEX idx_threads_deleted_at ON threads(deleted_at);
+CREATE INDEX idx_posts_deleted_at ON posts(deleted_at);
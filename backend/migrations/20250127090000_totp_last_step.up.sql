@@ -0,0 +1,2 @@
+-- Last TOTP time step accepted for the user, so a code cannot be used twice
+ALTER TABLE users ADD COLUMN totp_last_step BIGINT;
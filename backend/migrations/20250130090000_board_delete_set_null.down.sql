@@ -0,0 +1,3 @@
+ALTER TABLE threads DROP CONSTRAINT threads_board_id_fkey;
+ALTER TABLE threads ADD CONSTRAINT threads_board_id_fkey
+    FOREIGN KEY (board_id) REFERENCES boards(id);
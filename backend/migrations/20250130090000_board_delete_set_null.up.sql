@@ -0,0 +1,4 @@
+-- Deleting a board leaves its threads without one, like those created before boards existed
+ALTER TABLE threads DROP CONSTRAINT threads_board_id_fkey;
+ALTER TABLE threads ADD CONSTRAINT threads_board_id_fkey
+    FOREIGN KEY (board_id) REFERENCES boards(id) ON DELETE SET NULL;
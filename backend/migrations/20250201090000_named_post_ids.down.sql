@@ -0,0 +1 @@
+-- The poster ids of named posts cannot be derived again, they stay cleared
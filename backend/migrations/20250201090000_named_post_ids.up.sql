@@ -0,0 +1,2 @@
+-- Poster ids are only for anonymous posts, on named ones they unmask the author's anonymous posts
+UPDATE posts SET poster_id = NULL WHERE author_id IS NOT NULL;
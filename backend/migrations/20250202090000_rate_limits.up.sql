@@ -0,0 +1,11 @@
+-- Requests per subject in fixed windows, for limits which never escalate
+CREATE TABLE rate_limits (
+    subject VARCHAR(512) NOT NULL,
+    window_start TIMESTAMP WITH TIME ZONE NOT NULL,
+    window_end TIMESTAMP WITH TIME ZONE NOT NULL,
+    hits INTEGER NOT NULL,
+    PRIMARY KEY (subject, window_start)
+);
+
+-- Secure tripcodes used to count as failed logins, release clients locked out by that
+DELETE FROM login_failures WHERE subject LIKE 'tripcode:%';
@@ -0,0 +1,6 @@
+-- Signed sessions ended before they expire, kept until they would have expired
+CREATE TABLE revoked_sessions (
+    id UUID PRIMARY KEY NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
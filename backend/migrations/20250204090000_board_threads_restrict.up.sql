@@ -0,0 +1,4 @@
+-- Boards with threads are only deleted after moving them, threads never lose their board
+ALTER TABLE threads DROP CONSTRAINT threads_board_id_fkey;
+ALTER TABLE threads ADD CONSTRAINT threads_board_id_fkey
+    FOREIGN KEY (board_id) REFERENCES boards(id);
@@ -1,14 +1,12 @@
 -- Insert test data for users
-INSERT INTO users (id, name, password_hash, created_at, last_active, salt)
+INSERT INTO users (id, name, password_hash, created_at, last_active)
 VALUES 
 ('12345678-1234-5678-1234-567812345678', 'Alice Johnson', '$2a$12$12345678901234567890abcdef1234567890abcdef1234', 
   CURRENT_TIMESTAMP - INTERVAL '2 years',
-  CURRENT_TIMESTAMP - INTERVAL '1 year',
-  'salt123'),
+  CURRENT_TIMESTAMP - INTERVAL '1 year'),
 ('87654321-8765-4321-8765-43218765432178', 'Bob Smith', '$2a$12$09876543210987654321098765432109876543210', 
   CURRENT_TIMESTAMP - INTERVAL '1 year',
-  CURRENT_TIMESTAMP - INTERVAL '6 months',
-  'salt456');
+  CURRENT_TIMESTAMP - INTERVAL '6 months');
 
 -- Insert test data for threads
 INSERT INTO threads (id, name, created_at, last_active)
@@ -0,0 +1,3 @@
+pub(crate) mod model;
+mod schema;
+pub mod service;
@@ -0,0 +1,130 @@
+use actix_web::Result;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::chrono::Utc, FromRow};
+use uuid::Uuid;
+
+use super::schema::BoardResponse;
+use crate::user::auth::MaybeUser;
+use crate::user::role::Role;
+
+#[derive(FromRow, Debug, Serialize, Deserialize)]
+pub struct Board {
+    pub id: Uuid,
+    pub slug: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub allow_anonymous: bool,
+    pub max_post_length: Option<i32>,
+    pub min_thread_role: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Board {
+    pub fn as_response(&self) -> BoardResponse {
+        BoardResponse {
+            id: self.id,
+            slug: self.slug.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            allow_anonymous: self.allow_anonymous,
+            max_post_length: self.max_post_length,
+            min_thread_role: self.min_thread_role(),
+            created_at: self.created_at,
+        }
+    }
+
+    /// Roles are constrained by the database, anything unknown is treated as the strictest
+    pub fn min_thread_role(&self) -> Option<Role> {
+        self.min_thread_role
+            .as_deref()
+            .map(|role| Role::parse(role).unwrap_or(Role::Admin))
+    }
+}
+
+/// Posting rules of a board, threads without one accept everything
+#[derive(Debug, Default)]
+pub struct BoardRules {
+    pub allow_anonymous: Option<bool>,
+    pub max_post_length: Option<i32>,
+}
+
+impl BoardRules {
+    /// Checks a post against the rules, `anonymous` if it has no author
+    pub fn check(&self, content: &str, anonymous: bool) -> Result<()> {
+        if anonymous && self.allow_anonymous == Some(false) {
+            return Err(actix_web::error::ErrorForbidden(
+                "Board does not allow anonymous posts",
+            ));
+        }
+        if let Some(max) = self.max_post_length {
+            if content.chars().count() > max as usize {
+                return Err(actix_web::error::ErrorUnprocessableEntity(format!(
+                    "Posts on this board are limited to {max} characters"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the user may open threads on a board with the given minimum role
+pub fn can_create_threads(min_role: Option<Role>, user: &MaybeUser) -> bool {
+    match min_role {
+        None => true,
+        Some(min_role) => user
+            .0
+            .as_ref()
+            .is_some_and(|auth| auth.user.role() >= min_role),
+    }
+}
+
+/// Slugs name a board in URLs, lowercase letters, digits and dashes only
+pub fn normalize_slug(slug: &str) -> Result<String> {
+    let slug = slug.trim().to_lowercase();
+    let valid = (1..=32).contains(&slug.len())
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !slug.starts_with('-')
+        && !slug.ends_with('-');
+    if valid {
+        Ok(slug)
+    } else {
+        Err(actix_web::error::ErrorUnprocessableEntity(
+            "Board slugs have 1 to 32 letters, digits or inner dashes",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_of_boards() {
+        let open = BoardRules::default();
+        assert!(open.check(&"a".repeat(10_000), true).is_ok());
+
+        let strict = BoardRules {
+            allow_anonymous: Some(false),
+            max_post_length: Some(5),
+        };
+        assert!(strict.check("hello", false).is_ok());
+        assert!(strict.check("hello", true).is_err());
+        assert!(strict.check("hello!", false).is_err());
+        // Counted in characters, not bytes
+        assert!(strict.check("äöüßé", false).is_ok());
+    }
+
+    #[test]
+    fn test_normalize_slug() {
+        assert_eq!(normalize_slug(" Tech ").unwrap(), "tech");
+        assert_eq!(normalize_slug("off-topic2").unwrap(), "off-topic2");
+        assert!(normalize_slug("").is_err());
+        assert!(normalize_slug("-b").is_err());
+        assert!(normalize_slug("a b").is_err());
+        assert!(normalize_slug("über").is_err());
+        assert!(normalize_slug(&"a".repeat(33)).is_err());
+    }
+}
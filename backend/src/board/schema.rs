@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::user::role::Role;
+
+/// Creates a board, or replaces all settings of one when updating
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BoardRequest {
+    pub slug: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "allow_anonymous_default")]
+    pub allow_anonymous: bool,
+    /// Longest post in characters, `None` for no limit
+    #[serde(default)]
+    pub max_post_length: Option<i32>,
+    /// Lowest role allowed to open threads, `None` lets anyone do it
+    #[serde(default)]
+    pub min_thread_role: Option<Role>,
+}
+
+fn allow_anonymous_default() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BoardResponse {
+    pub id: Uuid,
+    pub slug: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub allow_anonymous: bool,
+    pub max_post_length: Option<i32>,
+    pub min_thread_role: Option<Role>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteBoardQuery {
+    pub id: Uuid,
+    /// Board the threads are moved to, needed unless the board has none
+    #[serde(default)]
+    pub move_to: Option<Uuid>,
+}
@@ -0,0 +1,247 @@
+use actix_web::middleware::from_fn;
+use actix_web::{delete, get, patch, post, web, HttpResponse, Responder, Result};
+use chrono::Utc;
+use log::{error, info};
+
+use uuid::Uuid;
+
+use crate::board::model::{normalize_slug, Board};
+use crate::board::schema::{BoardRequest, BoardResponse, DeleteBoardQuery};
+use crate::common::id::IdQuery;
+use crate::user::auth::AuthenticatedUser;
+use crate::user::csrf;
+use crate::user::role::Role;
+use crate::SharedState;
+
+#[post("")]
+async fn add(
+    auth: AuthenticatedUser,
+    body: web::Json<BoardRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    auth.require_role(Role::Admin)?;
+    let slug = check_request(&body)?;
+    match sqlx::query_as!(
+        Board,
+        "INSERT INTO boards (id, slug, name, description, allow_anonymous, max_post_length, min_thread_role, created_at)
+        VALUES($1,$2,$3,$4,$5,$6,$7,$8) RETURNING *;",
+        Uuid::new_v4(),
+        slug,
+        body.name,
+        body.description,
+        body.allow_anonymous,
+        body.max_post_length,
+        body.min_thread_role.map(|role| role.as_str()),
+        Utc::now(),
+    )
+    .fetch_one(&data.db)
+    .await
+    {
+        Ok(board) => {
+            info!("Board /{}/ added by {}", board.slug, auth.user.name);
+            Ok(HttpResponse::Created().json(board.as_response()))
+        }
+        Err(err)
+            if err
+                .as_database_error()
+                .is_some_and(|err| err.is_unique_violation()) =>
+        {
+            Err(actix_web::error::ErrorConflict("Board slug is taken"))
+        }
+        Err(err) => {
+            error!("Adding board /{slug}/ failed: {err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[get("")]
+async fn list(data: web::Data<SharedState>) -> Result<impl Responder> {
+    match sqlx::query_as!(Board, "SELECT * FROM boards ORDER BY slug;")
+        .fetch_all(&data.db)
+        .await
+    {
+        Ok(boards) => Ok(HttpResponse::Ok().json(
+            boards
+                .iter()
+                .map(Board::as_response)
+                .collect::<Vec<BoardResponse>>(),
+        )),
+        Err(err) => {
+            error!("{err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[get("{slug}")]
+async fn get(path: web::Path<String>, data: web::Data<SharedState>) -> Result<impl Responder> {
+    match sqlx::query_as!(
+        Board,
+        "SELECT * FROM boards WHERE slug = $1;",
+        path.to_lowercase()
+    )
+    .fetch_optional(&data.db)
+    .await
+    {
+        Ok(Some(board)) => Ok(HttpResponse::Ok().json(board.as_response())),
+        Ok(None) => Err(actix_web::error::ErrorNotFound("Board not found")),
+        Err(err) => {
+            error!("{err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[patch("")]
+async fn update(
+    query: web::Query<IdQuery>,
+    auth: AuthenticatedUser,
+    body: web::Json<BoardRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    auth.require_role(Role::Admin)?;
+    let slug = check_request(&body)?;
+    match sqlx::query_as!(
+        Board,
+        "UPDATE boards SET slug = $2, name = $3, description = $4, allow_anonymous = $5,
+        max_post_length = $6, min_thread_role = $7 WHERE id = $1 RETURNING *;",
+        query.id,
+        slug,
+        body.name,
+        body.description,
+        body.allow_anonymous,
+        body.max_post_length,
+        body.min_thread_role.map(|role| role.as_str()),
+    )
+    .fetch_optional(&data.db)
+    .await
+    {
+        Ok(Some(board)) => {
+            info!("Board /{}/ updated by {}", board.slug, auth.user.name);
+            Ok(HttpResponse::Ok().json(board.as_response()))
+        }
+        Ok(None) => Err(actix_web::error::ErrorNotFound("Board not found")),
+        Err(err)
+            if err
+                .as_database_error()
+                .is_some_and(|err| err.is_unique_violation()) =>
+        {
+            Err(actix_web::error::ErrorConflict("Board slug is taken"))
+        }
+        Err(err) => {
+            error!("Updating board {} failed: {err}", query.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+/// A board with threads needs `move_to`, the board all of them are moved to.
+/// Left without a board they would no longer follow its rules.
+#[delete("")]
+async fn delete(
+    query: web::Query<DeleteBoardQuery>,
+    auth: AuthenticatedUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    auth.require_role(Role::Admin)?;
+    if query.move_to == Some(query.id) {
+        return Err(actix_web::error::ErrorUnprocessableEntity(
+            "Cannot move threads to the board being deleted",
+        ));
+    }
+    let deleted = async {
+        let mut tx = data.db.begin().await?;
+        // Locked first, so no thread can be added while the board is emptied
+        let board =
+            sqlx::query_scalar!("SELECT id FROM boards WHERE id = $1 FOR UPDATE;", query.id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if board.is_none() {
+            return Ok(Ok(None));
+        }
+        if query.move_to.is_none() {
+            let has_threads = sqlx::query_scalar!(
+                r#"SELECT EXISTS(SELECT 1 FROM threads WHERE board_id = $1) AS "exists!";"#,
+                query.id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            if has_threads {
+                return Ok(Err(actix_web::error::ErrorConflict(
+                    "Board still has threads, pass move_to with the board to move them to",
+                )));
+            }
+        }
+        if let Some(move_to) = query.move_to {
+            let target =
+                sqlx::query_scalar!("SELECT id FROM boards WHERE id = $1 FOR SHARE;", move_to)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            if target.is_none() {
+                return Ok(Err(actix_web::error::ErrorNotFound(
+                    "Board to move the threads to not found",
+                )));
+            }
+            sqlx::query!(
+                "UPDATE threads SET board_id = $2 WHERE board_id = $1;",
+                query.id,
+                move_to
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        let board = sqlx::query_as!(
+            Board,
+            "DELETE FROM boards WHERE id = $1 RETURNING *;",
+            query.id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Ok(board))
+    }
+    .await;
+    match deleted {
+        Ok(Ok(Some(board))) => {
+            info!("Board /{}/ deleted by {}", board.slug, auth.user.name);
+            Ok(HttpResponse::Ok().json(board.as_response()))
+        }
+        Ok(Ok(None)) => Err(actix_web::error::ErrorNotFound("Board not found")),
+        Ok(Err(err)) => Err(err),
+        Err(err) => {
+            error!("Deleting board {} failed: {err}", query.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+/// Validates the settings of a board, returns the normalized slug
+fn check_request(body: &BoardRequest) -> Result<String> {
+    if body.name.trim().is_empty() {
+        return Err(actix_web::error::ErrorUnprocessableEntity(
+            "Board name cannot be empty",
+        ));
+    }
+    if body.max_post_length.is_some_and(|max| max <= 0) {
+        return Err(actix_web::error::ErrorUnprocessableEntity(
+            "Maximum post length has to be positive",
+        ));
+    }
+    normalize_slug(&body.slug)
+}
+
+pub fn board_service(conf: &mut web::ServiceConfig) {
+    let scope = web::scope("api/boards")
+        .wrap(from_fn(csrf::protect))
+        .service(add)
+        .service(list)
+        .service(get)
+        .service(update)
+        .service(delete);
+
+    conf.service(scope);
+}
@@ -0,0 +1,14 @@
+use actix_web::HttpRequest;
+
+use super::config::Config;
+
+/// Address of the client, proxy headers are only used when configured to be trusted
+pub fn address(req: &HttpRequest, config: &Config) -> String {
+    let info = req.connection_info();
+    let address = if config.trust_proxy_headers {
+        info.realip_remote_addr()
+    } else {
+        info.peer_addr()
+    };
+    address.unwrap_or("unknown").to_string()
+}
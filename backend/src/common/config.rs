@@ -0,0 +1,175 @@
+use std::{env, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+use chrono::Duration;
+
+use crate::thread::archive::ArchivePolicy;
+use crate::user::invite::RegistrationMode;
+use crate::user::lockout::LockoutPolicy;
+use crate::user::oidc::OidcConfig;
+use crate::user::session::{SessionBackend, SessionKey};
+
+/// Shortest accepted key for signed sessions, in bytes
+const MIN_SESSION_KEY_LEN: usize = 32;
+/// Shortest accepted tripcode pepper, in bytes
+const MIN_TRIPCODE_PEPPER_LEN: usize = 16;
+
+/// Runtime settings of the backend, read once from environment variables at startup
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Argon2 cost parameters used for new password hashes, hashes made with
+    /// other parameters are upgraded on the next successful login
+    pub argon2: argon2::Params,
+    /// Limits on failed logins per username and per client address
+    pub login_lockout: LockoutPolicy,
+    /// Take the client address from `Forwarded`/`X-Forwarded-For`, only enable
+    /// this behind a reverse proxy which sets these headers
+    pub trust_proxy_headers: bool,
+    /// Username which becomes admin as long as there is no admin yet, either at
+    /// startup or when it gets registered
+    pub bootstrap_admin: Option<String>,
+    /// Where login sessions are kept
+    pub session_backend: SessionBackend,
+    /// Who may register new accounts
+    pub registration_mode: RegistrationMode,
+    /// Whether regular users may create invites, staff always can
+    pub users_can_invite: bool,
+    /// Server secret mixed into tripcodes, tripcodes are disabled without it
+    pub tripcode_pepper: Option<String>,
+    /// Posts after which a thread stops being bumped to the top
+    pub bump_limit: i64,
+    /// When inactive threads are archived and deleted
+    pub archive: ArchivePolicy,
+    /// Days deleted threads and posts are kept for moderators to restore them
+    pub purge_deleted_after_days: i64,
+    /// OpenID Connect provider for single sign-on, only local logins without it
+    pub oidc: Option<OidcConfig>,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let argon2 = argon2::Params::new(
+            env_or("ARGON2_MEMORY_KIB", argon2::Params::DEFAULT_M_COST)?,
+            env_or("ARGON2_ITERATIONS", argon2::Params::DEFAULT_T_COST)?,
+            env_or("ARGON2_PARALLELISM", argon2::Params::DEFAULT_P_COST)?,
+            None,
+        )
+        .map_err(|err| anyhow!("Invalid Argon2 parameters: {err}"))?;
+
+        let login_lockout = LockoutPolicy {
+            max_failures: env_or("LOGIN_MAX_FAILURES", 5)?,
+            lockout: Duration::seconds(env_or("LOGIN_LOCKOUT_SECONDS", 15 * 60)?),
+        };
+
+        let session_backend = match env_or("SESSION_STORE", "database".to_string())?.as_str() {
+            "database" => SessionBackend::Database,
+            "memory" => SessionBackend::Memory,
+            "signed" => SessionBackend::Signed {
+                keys: session_keys()?,
+            },
+            other => bail!("SESSION_STORE has invalid value \"{other}\""),
+        };
+
+        let tripcode_pepper = env::var("TRIPCODE_PEPPER")
+            .ok()
+            .filter(|pepper| !pepper.is_empty());
+        if tripcode_pepper
+            .as_ref()
+            .is_some_and(|pepper| pepper.len() < MIN_TRIPCODE_PEPPER_LEN)
+        {
+            bail!("TRIPCODE_PEPPER must be at least {MIN_TRIPCODE_PEPPER_LEN} characters");
+        }
+
+        Ok(Config {
+            argon2,
+            login_lockout,
+            trust_proxy_headers: env_or("TRUST_PROXY_HEADERS", false)?,
+            bootstrap_admin: env::var("BOOTSTRAP_ADMIN")
+                .ok()
+                .filter(|name| !name.trim().is_empty()),
+            session_backend,
+            registration_mode: env_or("REGISTRATION_MODE", RegistrationMode::Open)?,
+            users_can_invite: env_or("USERS_CAN_INVITE", true)?,
+            tripcode_pepper,
+            bump_limit: env_or("BUMP_LIMIT", 300)?,
+            archive: ArchivePolicy {
+                inactive_days: env_opt("ARCHIVE_AFTER_DAYS")?,
+                max_active_threads: env_opt("MAX_ACTIVE_THREADS")?,
+                delete_after_days: env_opt("DELETE_ARCHIVED_AFTER_DAYS")?,
+            },
+            purge_deleted_after_days: env_or("PURGE_DELETED_AFTER_DAYS", 30)?,
+            oidc: oidc()?,
+        })
+    }
+}
+
+/// Keys for signed sessions from the comma separated `SESSION_KEYS`, newest first
+fn session_keys() -> anyhow::Result<Vec<SessionKey>> {
+    let keys: Vec<SessionKey> = env::var("SESSION_KEYS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| SessionKey(key.as_bytes().to_vec()))
+        .collect();
+    if keys.is_empty() {
+        bail!("SESSION_KEYS must be set when SESSION_STORE is \"signed\"");
+    }
+    if keys.iter().any(|key| key.0.len() < MIN_SESSION_KEY_LEN) {
+        bail!("SESSION_KEYS must be at least {MIN_SESSION_KEY_LEN} characters each");
+    }
+    Ok(keys)
+}
+
+/// OIDC settings, enabled by setting `OIDC_ISSUER`
+fn oidc() -> anyhow::Result<Option<OidcConfig>> {
+    let Some(issuer) = non_empty_var("OIDC_ISSUER") else {
+        return Ok(None);
+    };
+    let client_id = non_empty_var("OIDC_CLIENT_ID")
+        .context("OIDC_CLIENT_ID must be set when OIDC_ISSUER is")?;
+    let redirect_uri = non_empty_var("OIDC_REDIRECT_URI")
+        .context("OIDC_REDIRECT_URI must be set when OIDC_ISSUER is")?;
+    Ok(Some(OidcConfig {
+        issuer,
+        client_id,
+        client_secret: non_empty_var("OIDC_CLIENT_SECRET"),
+        redirect_uri,
+        scopes: env_or("OIDC_SCOPES", "openid profile".to_string())?,
+        frontend_url: env_or("OIDC_FRONTEND_URL", "http://localhost:5173/".to_string())?,
+        auto_provision: env_or("OIDC_AUTO_PROVISION", true)?,
+    }))
+}
+
+fn non_empty_var(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|value| !value.trim().is_empty())
+}
+
+/// Reads and parses an environment variable, falling back to `default` when it is not set
+fn env_or<T>(key: &str, default: T) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .with_context(|| format!("{key} has invalid value \"{value}\"")),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Reads and parses an optional environment variable, `None` when it is not set
+fn env_opt<T>(key: &str) -> anyhow::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match non_empty_var(key) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .with_context(|| format!("{key} has invalid value \"{value}\"")),
+        None => Ok(None),
+    }
+}
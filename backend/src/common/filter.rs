@@ -3,53 +3,86 @@ use log::debug;
 use serde::Deserialize;
 use uuid::Uuid;
 
-#[derive(Deserialize)]
+/// Order of listed rows, newest first either way
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    /// By creation time
+    #[default]
+    Created,
+    /// By last bump, only threads have one
+    Bump,
+}
+
+impl Sort {
+    fn column(&self) -> &'static str {
+        match self {
+            Sort::Created => "created_at",
+            Sort::Bump => "last_active",
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
 pub struct Filter {
     pub after: Option<DateTime<Utc>>,
     pub before: Option<DateTime<Utc>>,
     pub thread: Option<Uuid>,
     pub author: Option<Uuid>,
+    /// Only threads have a board
+    pub board: Option<Uuid>,
     pub limit: Option<i64>,
+    #[serde(default)]
+    pub sort: Sort,
 }
 impl Filter {
     /// To use this function pass a sql query e.q. "SELECT name FROM users"
     /// then it will process all the filters and add them as WHERE clause,
-    /// or LIMIT in case that is present
-    pub fn prepare(&self, mut query: String) -> String {
+    /// then ORDER BY and LIMIT in case that is present
+    pub fn prepare(&self, query: String) -> String {
+        self.prepare_with(query, &[], None)
+    }
+
+    /// Same as `prepare`, with fixed `conditions` added to those of the filter
+    /// and rows ordered by the `first` expression before the requested sort,
+    /// e.g. to hide archived threads and keep pinned ones on top
+    pub fn prepare_with(
+        &self,
+        mut query: String,
+        conditions: &[&str],
+        first: Option<&str>,
+    ) -> String {
+        let mut conditions: Vec<String> = conditions.iter().map(|cond| cond.to_string()).collect();
+        if let Some(start) = self.after {
+            conditions.push(format!("created_at >= '{}'", start));
+        }
+        if let Some(end) = self.before {
+            conditions.push(format!("created_at <= '{}'", end));
+        }
+        if let Some(thread) = self.thread {
+            conditions.push(format!("thread_id = '{}'", thread));
+        }
+        if let Some(user) = self.author {
+            conditions.push(format!("author_id = '{}'", user));
+        }
+        if let Some(board) = self.board {
+            conditions.push(format!("board_id = '{}'", board));
+        }
         // Add WHERE clause if filter is applied
-        if self.after.is_some()
-            || self.before.is_some()
-            || self.thread.is_some()
-            || self.author.is_some()
-        {
+        if !conditions.is_empty() {
             query.push_str(" WHERE ");
+            query.push_str(&conditions.join(" AND "));
+        }
 
-            let mut conditions = Vec::new();
-
-            if let Some(start) = self.after {
-                conditions.push(format!("created_at >= '{}'", start));
-            }
-            if let Some(end) = self.before {
-                conditions.push(format!("created_at <= '{}'", end));
-            }
-            if let Some(thread) = self.thread {
-                conditions.push(format!("thread_id = '{}'", thread));
-            }
-            if let Some(user) = self.author {
-                conditions.push(format!("author_id = '{}'", user));
-            }
-
-            for (i, cond) in conditions.iter().enumerate() {
-                if i > 0 {
-                    query.push_str(" AND ");
-                }
-                query.push_str(cond);
-            }
+        query.push_str(" ORDER BY ");
+        if let Some(first) = first {
+            query.push_str(&format!("{first}, "));
         }
+        query.push_str(&format!("{} DESC", self.sort.column()));
         if let Some(limit) = self.limit {
             query.push_str(&format!(" LIMIT {}", limit));
         }
-        query.push_str(" ORDER BY created_at DESC;");
+        query.push(';');
         debug!("{}", query);
         query
     }
@@ -62,13 +95,7 @@ mod tests {
 
     #[test]
     fn test_prepare_query_no_filters() {
-        let filter = Filter {
-            after: None,
-            before: None,
-            thread: None,
-            author: None,
-            limit: None,
-        };
+        let filter = Filter::default();
         assert_eq!(
             filter.prepare("SELECT * FROM posts".to_string()),
             "SELECT * FROM posts ORDER BY created_at DESC;"
@@ -80,16 +107,13 @@ mod tests {
         let start_timestamp = DateTime::from_timestamp(0, 0).unwrap();
         let filter = Filter {
             after: Some(start_timestamp),
-            before: None,
-            thread: None,
-            author: None,
-            limit: None,
+            ..Default::default()
         };
         assert_eq!(
             filter.prepare("SELECT * FROM posts".to_string()),
             format!(
                 "SELECT * FROM posts WHERE created_at >= '{}' ORDER BY created_at DESC;",
-                start_timestamp.to_string()
+                start_timestamp
             )
         );
     }
@@ -98,17 +122,14 @@ mod tests {
     fn test_prepare_query_with_end_timestamp() {
         let end_timestamp = DateTime::from_timestamp(0, 0).unwrap();
         let filter = Filter {
-            after: None,
             before: Some(end_timestamp),
-            thread: None,
-            author: None,
-            limit: None,
+            ..Default::default()
         };
         assert_eq!(
             filter.prepare("SELECT * FROM posts".to_string()),
             format!(
                 "SELECT * FROM posts WHERE created_at <= '{}' ORDER BY created_at DESC;",
-                end_timestamp.to_string()
+                end_timestamp
             )
         );
     }
@@ -117,11 +138,8 @@ mod tests {
     fn test_prepare_query_with_thread() {
         let thread = Uuid::new_v4();
         let filter = Filter {
-            after: None,
-            before: None,
             thread: Some(thread),
-            author: None,
-            limit: None,
+            ..Default::default()
         };
         assert_eq!(
             filter.prepare("SELECT * FROM posts".to_string()),
@@ -136,11 +154,8 @@ mod tests {
     fn test_prepare_query_with_user() {
         let user = Uuid::new_v4();
         let filter = Filter {
-            after: None,
-            before: None,
-            thread: None,
             author: Some(user),
-            limit: None,
+            ..Default::default()
         };
         assert_eq!(
             filter.prepare("SELECT * FROM posts".to_string()),
@@ -151,18 +166,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_prepare_query_with_board() {
+        let board = Uuid::new_v4();
+        let filter = Filter {
+            board: Some(board),
+            sort: Sort::Bump,
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.prepare("SELECT * FROM threads".to_string()),
+            format!(
+                "SELECT * FROM threads WHERE board_id = '{}' ORDER BY last_active DESC;",
+                board
+            )
+        );
+    }
+
     #[test]
     fn test_prepare_query_with_limit() {
         let filter = Filter {
-            after: None,
-            before: None,
-            thread: None,
-            author: None,
             limit: Some(10),
+            ..Default::default()
         };
         assert_eq!(
             filter.prepare("SELECT * FROM threads".to_string()),
-            "SELECT * FROM threads LIMIT 10 ORDER BY created_at DESC;"
+            "SELECT * FROM threads ORDER BY created_at DESC LIMIT 10;"
         );
     }
 
@@ -179,10 +208,40 @@ mod tests {
             thread: Some(thread),
             author: Some(user),
             limit: Some(limit),
+            ..Default::default()
         };
         assert_eq!(
             filter.prepare("SELECT * FROM posts".to_string()),
-            format!("SELECT * FROM posts WHERE created_at >= '{start_timestamp}' AND created_at <= '{end_timestamp}' AND thread_id = '{thread}' AND author_id = '{user}' LIMIT {limit} ORDER BY created_at DESC;")
+            format!("SELECT * FROM posts WHERE created_at >= '{start_timestamp}' AND created_at <= '{end_timestamp}' AND thread_id = '{thread}' AND author_id = '{user}' ORDER BY created_at DESC LIMIT {limit};")
+        );
+    }
+
+    #[test]
+    fn test_prepare_query_sorted_by_bump() {
+        let filter = Filter {
+            limit: Some(5),
+            sort: Sort::Bump,
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.prepare("SELECT * FROM threads".to_string()),
+            "SELECT * FROM threads ORDER BY last_active DESC LIMIT 5;"
+        );
+    }
+
+    #[test]
+    fn test_prepare_with_conditions_and_order() {
+        let filter = Filter {
+            sort: Sort::Bump,
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.prepare_with(
+                "SELECT * FROM threads".to_string(),
+                &["archived_at IS NULL"],
+                Some("pinned DESC")
+            ),
+            "SELECT * FROM threads WHERE archived_at IS NULL ORDER BY pinned DESC, last_active DESC;"
         );
     }
 }
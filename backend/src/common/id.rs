@@ -5,3 +5,11 @@ use uuid::Uuid;
 pub struct IdQuery {
     pub id: Uuid,
 }
+
+/// Deletes by id, the reason is kept with the tombstone
+#[derive(Deserialize)]
+pub struct DeleteQuery {
+    pub id: Uuid,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
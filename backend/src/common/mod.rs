@@ -1,2 +1,5 @@
+pub mod client;
+pub mod config;
 pub mod filter;
 pub mod id;
+pub mod rate_limit;
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use sqlx::{Pool, Postgres};
+
+/// Requests a subject may make per fixed window. Unlike `lockout` nothing
+/// escalates, a subject over the limit waits for the next window at most.
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    pub max_hits: i32,
+    pub window: Duration,
+}
+
+/// Start of the window `now` falls into
+pub fn window_start(now: DateTime<Utc>, window: Duration) -> DateTime<Utc> {
+    now.duration_trunc(window).unwrap_or(now)
+}
+
+/// Seconds until the window `now` falls into ends, rounded up so clients never
+/// retry a moment too early
+pub fn retry_after(now: DateTime<Utc>, window: Duration) -> i64 {
+    let remaining = window_start(now, window) + window - now;
+    (remaining.num_milliseconds() + 999) / 1000
+}
+
+/// Counts a request of the subject, returns the seconds to wait when it is over the limit
+pub async fn hit(
+    subject: &str,
+    limit: &RateLimit,
+    db: &Pool<Postgres>,
+) -> Result<Option<i64>, sqlx::Error> {
+    let now = Utc::now();
+    let start = window_start(now, limit.window);
+    let hits = sqlx::query_scalar!(
+        "INSERT INTO rate_limits (subject, window_start, window_end, hits) VALUES($1,$2,$3,1)
+        ON CONFLICT (subject, window_start) DO UPDATE SET hits = rate_limits.hits + 1
+        RETURNING hits;",
+        subject,
+        start,
+        start + limit.window,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok((hits > limit.max_hits).then(|| retry_after(now, limit.window)))
+}
+
+/// Removes windows which are over
+pub async fn delete_stale(db: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM rate_limits WHERE window_end <= $1;",
+        Utc::now()
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_window_start() {
+        let now = Utc.with_ymd_and_hms(2025, 2, 2, 9, 17, 42).unwrap();
+        assert_eq!(
+            window_start(now, Duration::minutes(10)),
+            Utc.with_ymd_and_hms(2025, 2, 2, 9, 10, 0).unwrap()
+        );
+        assert_eq!(
+            window_start(now, Duration::hours(1)),
+            Utc.with_ymd_and_hms(2025, 2, 2, 9, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_retry_after_never_exceeds_window() {
+        let start = Utc.with_ymd_and_hms(2025, 2, 2, 9, 10, 0).unwrap();
+        let window = Duration::minutes(10);
+        assert_eq!(retry_after(start, window), 600);
+        assert_eq!(
+            retry_after(start + Duration::milliseconds(1500), window),
+            599
+        );
+        assert_eq!(retry_after(start + Duration::seconds(599), window), 1);
+    }
+}
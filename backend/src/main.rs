@@ -1,4 +1,5 @@
-use std::{collections::HashMap, env, sync::Arc};
+use std::{env, sync::Arc};
+mod board;
 mod common;
 mod post;
 mod thread;
@@ -6,21 +7,22 @@ mod user;
 
 use actix_cors::Cors;
 use actix_web::{http::header, middleware::Logger, web::Data, App, HttpServer};
+use board::service::board_service;
+use common::config::Config;
 use log::{error, info};
 use post::service::post_service;
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 use thread::service::thread_service;
-use tokio::sync::Mutex;
-use user::service::user_service;
-use uuid::Uuid;
-
-type UserId = Uuid;
-type SessionId = Uuid;
+use user::oidc::OidcClient;
+use user::session::{self, SessionStore};
+use user::{role, service::user_service, username};
 
 #[derive(Clone)]
 pub struct SharedState {
     db: Pool<Postgres>,
-    user_sessions: Arc<Mutex<HashMap<SessionId, UserId>>>,
+    config: Config,
+    sessions: Arc<dyn SessionStore>,
+    oidc: Option<Arc<OidcClient>>,
 }
 
 #[tokio::main]
@@ -31,6 +33,13 @@ async fn main() -> anyhow::Result<()> {
     env_logger::init();
     info!("Backend started");
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            error!("Invalid configuration: {:#}", err);
+            std::process::exit(1)
+        }
+    };
 
     let pool = match PgPoolOptions::new()
         .max_connections(3)
@@ -47,8 +56,32 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
-    sqlx::migrate!();
-    let user_sessions = Arc::new(Mutex::new(HashMap::new())).clone();
+    if let Err(err) = sqlx::migrate!().run(&pool).await {
+        error!("Running migrations failed: {}", err);
+        std::process::exit(1)
+    }
+    username::backfill_keys(&pool).await;
+    if let Some(admin) = &config.bootstrap_admin {
+        match role::bootstrap_admin(&username::key(admin), &pool).await {
+            Ok(Some(user)) => info!("User {} bootstrapped as admin", user.name),
+            Ok(None) => {}
+            Err(err) => error!("Bootstrapping admin {admin} failed: {err}"),
+        }
+    }
+    let sessions = config.session_backend.build(&pool);
+    tokio::spawn(session::sweep_expired(pool.clone(), sessions.clone()));
+    if config.archive.is_enabled() {
+        tokio::spawn(thread::archive::run(pool.clone(), config.archive.clone()));
+    }
+    tokio::spawn(thread::purge::run(
+        pool.clone(),
+        config.purge_deleted_after_days,
+    ));
+    // Shared by all workers, so the provider metadata is only fetched once
+    let oidc = config
+        .oidc
+        .clone()
+        .map(|oidc| Arc::new(OidcClient::new(oidc)));
     HttpServer::new(move || {
         let cors = Cors::default()
             .allowed_origin("http://localhost:5173")
@@ -58,6 +91,7 @@ async fn main() -> anyhow::Result<()> {
                 header::CONTENT_TYPE,
                 header::AUTHORIZATION,
                 header::ACCEPT,
+                header::HeaderName::from_static("x-csrf-token"),
             ])
             .supports_credentials();
 
@@ -66,11 +100,14 @@ async fn main() -> anyhow::Result<()> {
             .wrap(cors)
             .app_data(Data::new(SharedState {
                 db: pool.clone(),
-                user_sessions: user_sessions.clone(),
+                config: config.clone(),
+                sessions: sessions.clone(),
+                oidc: oidc.clone(),
             }))
             .configure(user_service)
             .configure(post_service)
             .configure(thread_service)
+            .configure(board_service)
     })
     .bind(("0.0.0.0", 8080))?
     .run()
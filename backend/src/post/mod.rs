@@ -1,3 +1,5 @@
 pub(crate) mod model;
+pub(crate) mod poster_id;
 mod schema;
 pub mod service;
+mod tripcode;
@@ -10,4 +10,11 @@ pub struct Post {
     pub author_id: Option<Uuid>,
     pub content: String,
     pub created_at: DateTime<Utc>,
+    pub poster_id: Option<String>,
+    pub display_name: Option<String>,
+    pub tripcode: Option<String>,
+    pub sage: bool,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<Uuid>,
+    pub delete_reason: Option<String>,
 }
@@ -0,0 +1,88 @@
+use actix_web::{http::header, HttpRequest};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::common::{client, config::Config};
+use crate::user::auth::{Credential, MaybeUser};
+
+/// Characters of a poster id, 8 hex digits keep collisions within a thread unlikely
+const POSTER_ID_LEN: usize = 8;
+
+/// Identifies who is posting without storing anything about them. Sessions
+/// are preferred, clients without one fall back to their address and user agent.
+pub fn fingerprint(user: &MaybeUser, req: &HttpRequest, config: &Config) -> String {
+    match &user.0 {
+        Some(auth) => match auth.credential {
+            Credential::Session(session_id) => format!("session:{session_id}"),
+            Credential::ApiToken { .. } => format!("user:{}", auth.user.id),
+        },
+        None => {
+            let user_agent = req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            format!("client:{}:{user_agent}", client::address(req, config))
+        }
+    }
+}
+
+/// Poster id of a new post. Only anonymous posts get one, an id on a named post
+/// would tell everyone which anonymous posts in the thread are by the same user.
+pub fn for_post(
+    author_id: Option<Uuid>,
+    thread_id: Uuid,
+    fingerprint: &str,
+    thread_secret: &str,
+) -> Option<String> {
+    author_id
+        .is_none()
+        .then(|| derive(thread_id, fingerprint, thread_secret))
+}
+
+/// Derives the poster id of a fingerprint in a thread. Keyed with the secret of
+/// the thread, ids cannot be linked across threads or traced back to the fingerprint.
+pub fn derive(thread_id: Uuid, fingerprint: &str, thread_secret: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(thread_secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(thread_id.as_bytes());
+    mac.update(fingerprint.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .take(POSTER_ID_LEN / 2)
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_is_stable_within_thread() {
+        let thread_id = Uuid::new_v4();
+        let id = derive(thread_id, "session:a", "secret");
+        assert_eq!(id.len(), POSTER_ID_LEN);
+        assert_eq!(id, derive(thread_id, "session:a", "secret"));
+        assert_ne!(id, derive(thread_id, "session:b", "secret"));
+    }
+
+    #[test]
+    fn test_derive_differs_across_threads() {
+        let id = derive(Uuid::new_v4(), "session:a", "secret");
+        assert_ne!(id, derive(Uuid::new_v4(), "session:a", "secret"));
+        assert_ne!(id, derive(Uuid::new_v4(), "session:a", "other secret"));
+    }
+
+    #[test]
+    fn test_named_post_does_not_share_id_with_anonymous_post() {
+        let thread_id = Uuid::new_v4();
+        let named = for_post(Some(Uuid::new_v4()), thread_id, "session:a", "secret");
+        let anonymous = for_post(None, thread_id, "session:a", "secret");
+        assert_eq!(named, None);
+        assert_eq!(anonymous, Some(derive(thread_id, "session:a", "secret")));
+        assert_ne!(named, anonymous);
+    }
+}
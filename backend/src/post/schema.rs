@@ -5,14 +5,16 @@ use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AddPostRequest {
+    /// Has to match the logged in user, `None` posts anonymously
     pub author_id: Option<Uuid>,
     pub thread_id: Uuid,
     pub content: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DeletePostRequest {
-    pub id: Uuid,
+    /// Name of an anonymous post, `name#secret` or `name##secret` adds a tripcode
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Do not bump the thread
+    #[serde(default)]
+    pub sage: bool,
 }
 
 #[derive(FromRow, Debug, Serialize, Deserialize)]
@@ -23,4 +25,13 @@ pub struct PostResponse {
     pub author_name: String,
     pub content: String,
     pub created_at: DateTime<Utc>,
+    /// Pseudonym of an anonymous poster, the same for all their anonymous posts in
+    /// the thread. Named posts have none
+    pub poster_id: Option<String>,
+    /// Proves that anonymous posts were written by the same person
+    pub tripcode: Option<String>,
+    pub sage: bool,
+    /// Set for tombstones of deleted posts, which have no content or author
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub delete_reason: Option<String>,
 }
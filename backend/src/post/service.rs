@@ -1,36 +1,139 @@
-use actix_web::{delete, get, post, web, HttpResponse, Responder, Result};
+use actix_web::middleware::from_fn;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder, Result};
 use chrono::Utc;
 use log::{error, info};
+use sqlx::PgConnection;
 
 use uuid::Uuid;
 
-use crate::common::filter::Filter;
-use crate::common::id::IdQuery;
+use crate::board::model::BoardRules;
+use crate::common::filter::{Filter, Sort};
+use crate::common::id::{DeleteQuery, IdQuery};
+use crate::common::{client, rate_limit};
 use crate::post::model::Post;
 use crate::post::schema::{AddPostRequest, PostResponse};
+use crate::post::{poster_id, tripcode};
+use crate::user::auth::{AuthenticatedUser, MaybeUser};
+use crate::user::csrf;
+use crate::user::error::Error;
+use crate::user::role::Role;
+use crate::user::token::Scope;
+use crate::user::username;
 use crate::SharedState;
 
+/// Why a thread does not take a new post
+enum Rejected {
+    NotFound,
+    Locked,
+    Archived,
+    /// Breaks a rule of the board the thread is on
+    Board(actix_web::Error),
+    /// Invalid name or tripcode
+    Signature(actix_web::Error),
+}
+
 #[post("")]
 async fn add_post(
+    req: HttpRequest,
     body: web::Json<AddPostRequest>,
+    user: MaybeUser,
     data: web::Data<SharedState>,
 ) -> Result<impl Responder> {
-    match sqlx::query_as!(
-        Post,
-        "INSERT INTO posts VALUES($1,$2,$3,$4,$5) RETURNING *;",
-        Uuid::new_v4(),
-        body.thread_id,
-        body.author_id,
-        body.content,
-        Utc::now(),
-    )
-    .fetch_one(&data.db)
-    .await
-    {
-        Ok(post) => {
+    if let Some(auth) = &user.0 {
+        auth.require(Scope::PostsWrite)?;
+    }
+    check_author(body.author_id, &user)?;
+    let fingerprint = poster_id::fingerprint(&user, &req, &data.config);
+
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        // Locking the thread keeps concurrent posts from bumping past the limit
+        let Some(thread) = sqlx::query!(
+            "SELECT threads.poster_secret, threads.locked, threads.archived_at, threads.deleted_at,
+                boards.allow_anonymous AS \"allow_anonymous?\", boards.max_post_length
+            FROM threads LEFT JOIN boards ON boards.id = threads.board_id
+            WHERE threads.id=$1 FOR UPDATE OF threads;",
+            body.thread_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(Err(Rejected::NotFound));
+        };
+        if thread.deleted_at.is_some() {
+            return Ok(Err(Rejected::NotFound));
+        }
+        if thread.archived_at.is_some() {
+            return Ok(Err(Rejected::Archived));
+        }
+        // Staff can still answer in locked threads, e.g. to explain why
+        if thread.locked
+            && !user
+                .0
+                .as_ref()
+                .is_some_and(|auth| auth.user.role().is_staff())
+        {
+            return Ok(Err(Rejected::Locked));
+        }
+        let rules = BoardRules {
+            allow_anonymous: thread.allow_anonymous,
+            max_post_length: thread.max_post_length,
+        };
+        if let Err(err) = rules.check(&body.content, body.author_id.is_none()) {
+            return Ok(Err(Rejected::Board(err)));
+        }
+        // Signed last, so rejected posts do not count against the secure tripcode limit
+        let (display_name, tripcode) =
+            match signature(body.name.as_deref(), body.author_id, &user, &req, &data).await {
+                Ok(signature) => signature,
+                Err(err) => return Ok(Err(Rejected::Signature(err))),
+            };
+        let post = Post {
+            id: Uuid::new_v4(),
+            thread_id: body.thread_id,
+            author_id: body.author_id,
+            content: body.content.clone(),
+            created_at: Utc::now(),
+            poster_id: poster_id::for_post(
+                body.author_id,
+                body.thread_id,
+                &fingerprint,
+                &thread.poster_secret,
+            ),
+            display_name,
+            tripcode,
+            sage: body.sage,
+            deleted_at: None,
+            deleted_by: None,
+            delete_reason: None,
+        };
+        let post = insert(&post, &mut tx).await?;
+        if !post.sage {
+            // The count includes the new post, so the post reaching the limit still bumps
+            sqlx::query!(
+                "UPDATE threads SET last_active = $2 WHERE id = $1
+                AND (SELECT COUNT(*) FROM posts WHERE thread_id = $1) <= $3;",
+                post.thread_id,
+                post.created_at,
+                data.config.bump_limit,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Ok(post))
+    }
+    .await;
+
+    match result {
+        Ok(Ok(post)) => {
             info!("Post {} added successfully", body.content);
             Ok(HttpResponse::Created().json(post))
         }
+        Ok(Err(Rejected::NotFound)) => Err(actix_web::error::ErrorNotFound("Thread not found")),
+        Ok(Err(Rejected::Locked)) => Err(actix_web::error::ErrorLocked("Thread is locked")),
+        Ok(Err(Rejected::Archived)) => Err(actix_web::error::ErrorLocked("Thread is archived")),
+        Ok(Err(Rejected::Board(err))) | Ok(Err(Rejected::Signature(err))) => Err(err),
         Err(err) => {
             error!("{err}");
             Err(actix_web::error::ErrorInternalServerError(err))
@@ -43,25 +146,50 @@ async fn get_posts(
     data: web::Data<SharedState>,
     query: web::Query<Filter>,
 ) -> Result<impl Responder> {
-    let query_string = query.prepare(
-        "SELECT 
+    if query.sort == Sort::Bump {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Only threads can be sorted by bump",
+        ));
+    }
+    if query.board.is_some() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Only threads can be filtered by board",
+        ));
+    }
+    // Deleted posts stay in place as tombstones, without anything of their content or author
+    let select = "SELECT 
             posts.id, 
             posts.thread_id, 
-            COALESCE(posts.author_id, '00000000-0000-0000-0000-000000000000') AS author_id,
+            CASE
+                WHEN posts.deleted_at IS NULL
+                THEN COALESCE(posts.author_id, '00000000-0000-0000-0000-000000000000')
+                ELSE '00000000-0000-0000-0000-000000000000'
+            END AS author_id,
             CASE 
-                WHEN posts.author_id IS NULL THEN 'Anonymous' 
+                WHEN posts.deleted_at IS NOT NULL THEN 'Deleted'
+                WHEN posts.author_id IS NULL THEN COALESCE(posts.display_name, 'Anonymous') 
                 ELSE users.name 
             END AS author_name,
-            posts.content, 
-            posts.created_at
+            CASE WHEN posts.deleted_at IS NULL THEN posts.content ELSE '' END AS content, 
+            posts.created_at,
+            CASE WHEN posts.deleted_at IS NULL AND posts.author_id IS NULL THEN posts.poster_id END AS poster_id,
+            CASE WHEN posts.deleted_at IS NULL THEN posts.tripcode END AS tripcode,
+            posts.sage,
+            posts.deleted_at,
+            posts.delete_reason
         FROM 
             posts
         LEFT JOIN 
             users 
         ON 
                 posts.author_id = users.id"
-            .to_string(),
-    );
+        .to_string();
+    // Listing the posts of an author must not reveal which tombstones were theirs
+    let query_string = if query.author.is_some() {
+        query.prepare_with(select, &["posts.deleted_at IS NULL"], None)
+    } else {
+        query.prepare(select)
+    };
     let query_result: Vec<PostResponse> =
         match sqlx::query_as(&query_string).fetch_all(&data.db).await {
             Ok(users) => users,
@@ -75,15 +203,46 @@ async fn get_posts(
 
 #[delete("")]
 async fn delete(
-    query: web::Query<IdQuery>,
+    query: web::Query<DeleteQuery>,
+    auth: AuthenticatedUser,
     data: web::Data<SharedState>,
 ) -> Result<impl Responder> {
-    match sqlx::query_as!(Post, "DELETE FROM posts WHERE id=$1;", query.id.clone())
-        .execute(&data.db)
-        .await
+    auth.require(Scope::PostsWrite)?;
+    match sqlx::query_scalar!(
+        "SELECT author_id FROM posts WHERE id=$1 AND deleted_at IS NULL;",
+        query.id
+    )
+    .fetch_optional(&data.db)
+    .await
+    {
+        Ok(Some(author_id)) if auth.can_moderate(author_id) => {}
+        Ok(Some(_)) => {
+            return Err(actix_web::error::ErrorForbidden(
+                "Post belongs to another user",
+            ))
+        }
+        Ok(None) => return Err(actix_web::error::ErrorNotFound("Post not found")),
+        Err(err) => {
+            error!("Looking up Post {} failed: {err}", query.id);
+            return Err(actix_web::error::ErrorInternalServerError(err));
+        }
+    }
+    match sqlx::query!(
+        "UPDATE posts SET deleted_at = $2, deleted_by = $3, delete_reason = $4
+        WHERE id=$1 AND deleted_at IS NULL;",
+        query.id,
+        Utc::now(),
+        auth.user.id,
+        query.reason,
+    )
+    .execute(&data.db)
+    .await
     {
         Ok(_) => {
-            info!("Post {} deleted successfully", query.id);
+            info!(
+                "Post {} deleted successfully by {}",
+                query.id, auth.user.name
+            );
             Ok(HttpResponse::Ok())
         }
         Err(err) => {
@@ -93,8 +252,195 @@ async fn delete(
     }
 }
 
+#[post("restore")]
+async fn restore(
+    query: web::Query<IdQuery>,
+    auth: AuthenticatedUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.require(Scope::PostsWrite)?;
+    auth.require_role(Role::Moderator)?;
+    match sqlx::query!(
+        "SELECT threads.deleted_at AS thread_deleted_at FROM posts
+        JOIN threads ON threads.id = posts.thread_id
+        WHERE posts.id = $1 AND posts.deleted_at IS NOT NULL;",
+        query.id
+    )
+    .fetch_optional(&data.db)
+    .await
+    {
+        Ok(Some(post)) if post.thread_deleted_at.is_some() => {
+            return Err(actix_web::error::ErrorConflict(
+                "Thread of the post is deleted, restore the thread instead",
+            ))
+        }
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(actix_web::error::ErrorNotFound("Deleted post not found")),
+        Err(err) => {
+            error!("Looking up Post {} failed: {err}", query.id);
+            return Err(actix_web::error::ErrorInternalServerError(err));
+        }
+    }
+    match sqlx::query_as!(
+        Post,
+        "UPDATE posts SET deleted_at = NULL, deleted_by = NULL, delete_reason = NULL
+        WHERE id = $1 RETURNING *;",
+        query.id
+    )
+    .fetch_one(&data.db)
+    .await
+    {
+        Ok(post) => {
+            info!("Post {} restored by {}", query.id, auth.user.name);
+            Ok(HttpResponse::Ok().json(post))
+        }
+        Err(err) => {
+            error!("Restoring Post {} failed: {err}", query.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+/// Posts without author_id stay anonymous, otherwise it has to match the session
+pub(crate) fn check_author(author_id: Option<Uuid>, user: &MaybeUser) -> Result<()> {
+    let Some(author_id) = author_id else {
+        return Ok(());
+    };
+    match user.id() {
+        Some(user_id) if user_id == author_id => Ok(()),
+        Some(_) => Err(actix_web::error::ErrorForbidden(
+            "Cannot post as another user",
+        )),
+        None => Err(actix_web::error::ErrorUnauthorized("Invalid session")),
+    }
+}
+
+/// Display name and tripcode of a post from its optional `name` field
+pub(crate) async fn signature(
+    name: Option<&str>,
+    author_id: Option<Uuid>,
+    user: &MaybeUser,
+    req: &HttpRequest,
+    data: &SharedState,
+) -> Result<(Option<String>, Option<String>)> {
+    // Names and tripcodes are for anonymous posts, registered users already have both
+    match name {
+        Some(_) if author_id.is_some() => Err(actix_web::error::ErrorBadRequest(
+            "Names can only be given to anonymous posts",
+        )),
+        Some(raw) => sign(raw, user, req, data).await,
+        None => Ok((None, None)),
+    }
+}
+
+pub(crate) async fn insert(post: &Post, conn: &mut PgConnection) -> Result<Post, sqlx::Error> {
+    sqlx::query_as!(
+        Post,
+        "INSERT INTO posts (id, thread_id, author_id, content, created_at, poster_id, display_name, tripcode, sage)
+        VALUES($1,$2,$3,$4,$5,$6,$7,$8,$9) RETURNING *;",
+        post.id,
+        post.thread_id,
+        post.author_id,
+        post.content,
+        post.created_at,
+        post.poster_id,
+        post.display_name,
+        post.tripcode,
+        post.sage,
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Turns a `name#secret` field into the display name and tripcode of a post
+async fn sign(
+    raw: &str,
+    user: &MaybeUser,
+    req: &HttpRequest,
+    data: &SharedState,
+) -> Result<(Option<String>, Option<String>)> {
+    let signature = tripcode::parse(raw);
+    let name = match signature.name {
+        Some(name) => {
+            // Same rules as usernames, anonymous posts may not pass as a registered user
+            let name = username::normalize(name)?;
+            match sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM users WHERE name_key = $1);",
+                username::key(&name)
+            )
+            .fetch_one(&data.db)
+            .await
+            {
+                Ok(Some(false)) => Some(name),
+                Ok(_) => return Err(Error::UsernameTaken.into()),
+                Err(err) => {
+                    error!("Checking name {name} failed: {err}");
+                    return Err(actix_web::error::ErrorInternalServerError(err));
+                }
+            }
+        }
+        None => None,
+    };
+    let Some(secret) = signature.secret else {
+        return Ok((name, None));
+    };
+    let Some(pepper) = &data.config.tripcode_pepper else {
+        return Err(actix_web::error::ErrorUnprocessableEntity(
+            "Tripcodes are not enabled on this server",
+        ));
+    };
+    let tripcode = if signature.secure {
+        secure_tripcode(secret, pepper, user, req, data).await?
+    } else {
+        tripcode::normal(secret, pepper)
+    };
+    Ok((name, Some(tripcode)))
+}
+
+/// Hashes a `##` tripcode off the worker thread, rate limited since anyone can
+/// ask for one. Logged in users have their own limit, others share theirs with
+/// everyone behind the same address.
+async fn secure_tripcode(
+    secret: &str,
+    pepper: &str,
+    user: &MaybeUser,
+    req: &HttpRequest,
+    data: &SharedState,
+) -> Result<String> {
+    let subject = match user.id() {
+        Some(user_id) => format!("tripcode:user:{user_id}"),
+        None => format!("tripcode:client:{}", client::address(req, &data.config)),
+    };
+    match rate_limit::hit(&subject, &tripcode::secure_rate_limit(), &data.db).await {
+        Ok(None) => {}
+        Ok(Some(retry_after)) => return Err(Error::TooManyTripcodes { retry_after }.into()),
+        Err(err) => {
+            error!("Checking secure tripcode limit failed: {err}");
+            return Err(actix_web::error::ErrorInternalServerError(err));
+        }
+    }
+
+    let (secret, pepper) = (secret.to_string(), pepper.to_string());
+    match web::block(move || tripcode::secure(&secret, &pepper)).await {
+        Ok(Ok(tripcode)) => Ok(tripcode),
+        Ok(Err(err)) => {
+            error!("Hashing secure tripcode failed: {err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+        Err(err) => {
+            error!("Hashing secure tripcode failed: {err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
 pub fn post_service(conf: &mut web::ServiceConfig) {
-    let scope = web::scope("api/posts").service(add_post).service(get_posts);
+    let scope = web::scope("api/posts")
+        .wrap(from_fn(csrf::protect))
+        .service(add_post)
+        .service(get_posts)
+        .service(delete)
+        .service(restore);
 
     conf.service(scope);
 }
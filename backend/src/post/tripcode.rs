@@ -0,0 +1,140 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chrono::Duration;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::common::rate_limit::RateLimit;
+
+/// Characters of the hash part of a tripcode
+const TRIPCODE_LEN: usize = 10;
+const TRIPCODE_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789./";
+/// Argon2 cost of secure tripcodes, fixed so tripcodes survive changes of the password settings
+const SECURE_MEMORY_KIB: u32 = 19 * 1024;
+const SECURE_ITERATIONS: u32 = 2;
+
+/// Secure tripcodes hashed per poster, plenty for regulars but too few for guessing secrets
+pub fn secure_rate_limit() -> RateLimit {
+    RateLimit {
+        max_hits: 20,
+        window: Duration::minutes(10),
+    }
+}
+
+/// A `name#secret` or `name##secret` field split into its parts
+#[derive(Debug, PartialEq)]
+pub struct Signature<'a> {
+    /// Display name, `None` when only a tripcode was given
+    pub name: Option<&'a str>,
+    pub secret: Option<&'a str>,
+    /// `##` asks for the slow, brute force resistant variant
+    pub secure: bool,
+}
+
+pub fn parse(raw: &str) -> Signature<'_> {
+    let (name, secret, secure) = match raw.split_once('#') {
+        Some((name, secret)) => match secret.strip_prefix('#') {
+            Some(secret) => (name, Some(secret), true),
+            None => (name, Some(secret), false),
+        },
+        None => (raw, None, false),
+    };
+    let name = Some(name.trim()).filter(|name| !name.is_empty());
+    let secret = secret.filter(|secret| !secret.is_empty());
+    Signature {
+        name,
+        secret,
+        secure: secure && secret.is_some(),
+    }
+}
+
+fn encode(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take(TRIPCODE_LEN)
+        .map(|byte| TRIPCODE_ALPHABET[(*byte & 63) as usize] as char)
+        .collect()
+}
+
+/// Tripcode shown as `!xxxxxxxxxx`, a keyed hash of the secret with the server pepper
+pub fn normal(secret: &str, pepper: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(pepper.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(secret.as_bytes());
+    format!("!{}", encode(&mac.finalize().into_bytes()))
+}
+
+/// Tripcode shown as `!!xxxxxxxxxx`, an Argon2id hash of the secret keyed with
+/// the pepper, so short secrets stay expensive to guess even with the pepper known
+pub fn secure(secret: &str, pepper: &str) -> Result<String, argon2::Error> {
+    let params = Params::new(SECURE_MEMORY_KIB, SECURE_ITERATIONS, 1, Some(32))?;
+    let hasher = Argon2::new_with_secret(
+        pepper.as_bytes(),
+        Algorithm::Argon2id,
+        Version::V0x13,
+        params,
+    )?;
+    // Tripcodes have to be reproducible, so the salt is derived instead of random
+    let salt = Sha256::digest(format!("tripcode:{pepper}").as_bytes());
+    let mut output = [0u8; 32];
+    hasher.hash_password_into(secret.as_bytes(), &salt, &mut output)?;
+    Ok(format!("!!{}", encode(&output)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signature() {
+        assert_eq!(
+            parse(" Bob #hunter2"),
+            Signature {
+                name: Some("Bob"),
+                secret: Some("hunter2"),
+                secure: false
+            }
+        );
+        assert_eq!(
+            parse("##hunter2"),
+            Signature {
+                name: None,
+                secret: Some("hunter2"),
+                secure: true
+            }
+        );
+        assert_eq!(
+            parse("Bob"),
+            Signature {
+                name: Some("Bob"),
+                secret: None,
+                secure: false
+            }
+        );
+        assert_eq!(
+            parse("Bob##"),
+            Signature {
+                name: Some("Bob"),
+                secret: None,
+                secure: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_normal_tripcode() {
+        let tripcode = normal("hunter2", "pepper");
+        assert_eq!(tripcode.len(), TRIPCODE_LEN + 1);
+        assert_eq!(tripcode, normal("hunter2", "pepper"));
+        assert_ne!(tripcode, normal("hunter3", "pepper"));
+        assert_ne!(tripcode, normal("hunter2", "other pepper"));
+    }
+
+    #[test]
+    fn test_secure_tripcode() {
+        let tripcode = secure("hunter2", "pepper").unwrap();
+        assert!(tripcode.starts_with("!!"));
+        assert_eq!(tripcode, secure("hunter2", "pepper").unwrap());
+        assert_ne!(tripcode[2..], normal("hunter2", "pepper")[1..]);
+    }
+}
@@ -0,0 +1,94 @@
+use chrono::{Duration, Utc};
+use log::{error, info};
+use sqlx::{Pool, Postgres};
+
+/// How often the archive policy is applied
+const ARCHIVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// When threads get archived and deleted, every stage is optional. Pinned
+/// threads are never archived.
+#[derive(Clone, Debug, Default)]
+pub struct ArchivePolicy {
+    /// Archive threads which were not bumped for this many days
+    pub inactive_days: Option<i64>,
+    /// Archive threads which fell out of this many most recently bumped ones
+    pub max_active_threads: Option<i64>,
+    /// Delete archived threads and their posts after this many days
+    pub delete_after_days: Option<i64>,
+}
+
+impl ArchivePolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.inactive_days.is_some()
+            || self.max_active_threads.is_some()
+            || self.delete_after_days.is_some()
+    }
+}
+
+/// Periodically applies the archive policy, meant to be spawned once at startup
+pub async fn run(db: Pool<Postgres>, policy: ArchivePolicy) {
+    let mut interval = tokio::time::interval(ARCHIVE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match archive(&db, &policy).await {
+            Ok(0) => {}
+            Ok(count) => info!("Archived {count} threads"),
+            Err(err) => error!("Archiving threads failed: {err}"),
+        }
+        if let Some(days) = policy.delete_after_days {
+            match delete_archived(&db, days).await {
+                Ok(0) => {}
+                Ok(count) => info!("Deleted {count} archived threads"),
+                Err(err) => error!("Deleting archived threads failed: {err}"),
+            }
+        }
+    }
+}
+
+async fn archive(db: &Pool<Postgres>, policy: &ArchivePolicy) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+    let mut archived = 0;
+    if let Some(days) = policy.inactive_days {
+        archived += sqlx::query!(
+            "UPDATE threads SET archived_at = $1
+            WHERE archived_at IS NULL AND NOT pinned AND deleted_at IS NULL AND last_active < $2;",
+            now,
+            now - Duration::days(days),
+        )
+        .execute(db)
+        .await?
+        .rows_affected();
+    }
+    if let Some(max_active) = policy.max_active_threads {
+        archived += sqlx::query!(
+            "UPDATE threads SET archived_at = $1
+            WHERE archived_at IS NULL AND NOT pinned AND deleted_at IS NULL AND id NOT IN (
+                SELECT id FROM threads WHERE archived_at IS NULL AND NOT pinned AND deleted_at IS NULL
+                ORDER BY last_active DESC LIMIT $2
+            );",
+            now,
+            max_active,
+        )
+        .execute(db)
+        .await?
+        .rows_affected();
+    }
+    Ok(archived)
+}
+
+async fn delete_archived(db: &Pool<Postgres>, days: i64) -> Result<u64, sqlx::Error> {
+    let cutoff = Utc::now() - Duration::days(days);
+    let mut tx = db.begin().await?;
+    sqlx::query!(
+        "DELETE FROM posts WHERE thread_id IN (SELECT id FROM threads WHERE archived_at < $1);",
+        cutoff
+    )
+    .execute(&mut *tx)
+    .await?;
+    let deleted = sqlx::query!("DELETE FROM threads WHERE archived_at < $1;", cutoff)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(deleted)
+}
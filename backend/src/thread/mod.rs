@@ -1,3 +1,6 @@
+pub(crate) mod archive;
 mod model;
+pub(crate) mod purge;
 mod schema;
 pub mod service;
+pub(crate) mod tag;
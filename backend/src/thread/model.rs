@@ -9,4 +9,13 @@ pub struct Thread {
     pub name: String,
     pub created_at: DateTime<Utc>,
     pub last_active: DateTime<Utc>,
+    pub author_id: Option<Uuid>,
+    pub description: Option<String>,
+    pub pinned: bool,
+    pub locked: bool,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub board_id: Option<Uuid>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<Uuid>,
+    pub delete_reason: Option<String>,
 }
@@ -0,0 +1,40 @@
+use chrono::{Duration, Utc};
+use log::{error, info};
+use sqlx::{Pool, Postgres};
+
+/// How often deleted threads and posts are checked for purging
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Periodically removes threads and posts deleted more than `retention_days` ago
+/// for good, meant to be spawned once at startup
+pub async fn run(db: Pool<Postgres>, retention_days: i64) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match purge(&db, retention_days).await {
+            Ok((0, 0)) => {}
+            Ok((threads, posts)) => info!("Purged {threads} deleted threads and {posts} posts"),
+            Err(err) => error!("Purging deleted threads and posts failed: {err}"),
+        }
+    }
+}
+
+async fn purge(db: &Pool<Postgres>, retention_days: i64) -> Result<(u64, u64), sqlx::Error> {
+    let cutoff = Utc::now() - Duration::days(retention_days);
+    let mut tx = db.begin().await?;
+    // Posts of purged threads go as well, even those deleted later than the thread
+    let posts = sqlx::query!(
+        "DELETE FROM posts WHERE deleted_at < $1
+        OR thread_id IN (SELECT id FROM threads WHERE deleted_at < $1);",
+        cutoff
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let threads = sqlx::query!("DELETE FROM threads WHERE deleted_at < $1;", cutoff)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok((threads, posts))
+}
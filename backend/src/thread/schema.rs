@@ -1,17 +1,86 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
 use uuid::Uuid;
 
+use super::model::Thread;
+use super::tag::TagMatch;
+use crate::post::model::Post;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AddThreadRequest {
     pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Board to open the thread on, its settings decide who may do so
+    #[serde(default)]
+    pub board_id: Option<Uuid>,
+    /// Has to match the logged in user, `None` opens the thread anonymously
+    #[serde(default)]
+    pub author_id: Option<Uuid>,
+    /// Content of the opening post
+    pub content: String,
+    /// Name of an anonymous opening post, same as the `name` of a post
+    #[serde(default)]
+    pub poster_name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddThreadResponse {
+    #[serde(flatten)]
+    pub thread: Thread,
+    pub tags: Vec<String>,
+    pub opening_post: Post,
+}
+
+/// A thread together with its tags and statistics of its posts
+#[derive(FromRow, Debug, Serialize, Deserialize)]
+pub struct ThreadResponse {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub thread: Thread,
+    pub tags: Vec<String>,
+    pub post_count: i64,
+    /// Distinct users and anonymous posters, told apart by their poster id
+    pub participant_count: i64,
+    pub last_post_at: Option<DateTime<Utc>>,
+    /// Name shown with the last post
+    pub last_poster: Option<String>,
+    /// Beginning of the opening post
+    pub preview: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetPinnedRequest {
+    pub id: Uuid,
+    pub pinned: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct GetNThreadsRequest {
-    pub n: i64,
+pub struct SetLockedRequest {
+    pub id: Uuid,
+    pub locked: bool,
 }
 
+/// Replaces all tags of a thread
 #[derive(Debug, Serialize, Deserialize)]
-pub struct DeletePostSchema {
+pub struct SetTagsRequest {
     pub id: Uuid,
+    pub tags: Vec<String>,
+}
+
+/// Listing options besides the common `Filter`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThreadListQuery {
+    /// List archived threads instead of active ones
+    #[serde(default)]
+    pub archived: bool,
+    /// List deleted threads instead, only for moderators
+    #[serde(default)]
+    pub deleted: bool,
+    /// Whether threads need any or all tags given with `tag=`
+    #[serde(default, rename = "match")]
+    pub tag_match: TagMatch,
 }
@@ -1,34 +1,135 @@
-use actix_web::{delete, get, post, web, HttpResponse, Responder, Result};
+use actix_web::middleware::from_fn;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder, Result};
 use chrono::Utc;
 use log::{debug, error, info};
+use sqlx::PgConnection;
 
 use uuid::Uuid;
 
+use crate::board::model::{can_create_threads, Board, BoardRules};
 use crate::common::filter::Filter;
-use crate::common::id::IdQuery;
+use crate::common::id::{DeleteQuery, IdQuery};
+use crate::post::model::Post;
+use crate::post::poster_id;
+use crate::post::service as post_service;
 use crate::thread::model::Thread;
-use crate::thread::schema::AddThreadRequest;
+use crate::thread::schema::{
+    AddThreadRequest, AddThreadResponse, SetLockedRequest, SetPinnedRequest, SetTagsRequest,
+    ThreadListQuery, ThreadResponse,
+};
+use crate::thread::tag;
+use crate::user::auth::{AuthenticatedUser, MaybeUser};
+use crate::user::csrf;
+use crate::user::role::Role;
+use crate::user::token::Scope;
 use crate::SharedState;
 
+/// Characters of the opening post shown with a thread
+const PREVIEW_LENGTH: usize = 200;
+
 #[post("")]
 async fn add(
+    req: HttpRequest,
     body: web::Json<AddThreadRequest>,
+    user: MaybeUser,
     data: web::Data<SharedState>,
 ) -> Result<impl Responder> {
-    match sqlx::query_as!(
-        Thread,
-        "INSERT INTO threads VALUES($1,$2,$3) RETURNING *;",
-        Uuid::new_v4(),
-        body.name,
-        Utc::now(),
-    )
-    .fetch_one(&data.db)
-    .await
-    {
-        Ok(result) => {
-            info!("Thread \"{}\" added successfully", body.name);
-            Ok(HttpResponse::Created().json(result))
+    if let Some(auth) = &user.0 {
+        auth.require(Scope::ThreadsWrite)?;
+        auth.require(Scope::PostsWrite)?;
+    }
+    post_service::check_author(body.author_id, &user)?;
+    let tags = tag::normalize_all(&body.tags)?;
+    let fingerprint = poster_id::fingerprint(&user, &req, &data.config);
+
+    // A thread is never visible without its opening post
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        if let Some(board_id) = body.board_id {
+            if let Err(err) = check_board(board_id, &body, &user, &mut tx).await? {
+                return Ok(Err(err));
+            }
+        }
+        let (display_name, tripcode) = match post_service::signature(
+            body.poster_name.as_deref(),
+            body.author_id,
+            &user,
+            &req,
+            &data,
+        )
+        .await
+        {
+            Ok(signature) => signature,
+            Err(err) => return Ok(Err(err)),
+        };
+        let now = Utc::now();
+        let thread = sqlx::query_as!(
+            Thread,
+            "INSERT INTO threads (id, name, created_at, last_active, author_id, description, board_id)
+            VALUES($1,$2,$3,$4,$5,$6,$7)
+            RETURNING id, name, created_at, last_active, author_id, description, pinned, locked, archived_at, board_id,
+            deleted_at, deleted_by, delete_reason;",
+            Uuid::new_v4(),
+            body.name,
+            now,
+            now,
+            body.author_id,
+            body.description,
+            body.board_id,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let poster_secret = sqlx::query_scalar!(
+            "SELECT poster_secret FROM threads WHERE id = $1;",
+            thread.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let opening_post = post_service::insert(
+            &Post {
+                id: Uuid::new_v4(),
+                thread_id: thread.id,
+                author_id: body.author_id,
+                content: body.content.clone(),
+                created_at: now,
+                poster_id: poster_id::for_post(
+                    body.author_id,
+                    thread.id,
+                    &fingerprint,
+                    &poster_secret,
+                ),
+                display_name,
+                tripcode,
+                sage: false,
+                deleted_at: None,
+                deleted_by: None,
+                delete_reason: None,
+            },
+            &mut tx,
+        )
+        .await?;
+        let tags = tag::set(thread.id, &tags, &mut tx).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Ok(AddThreadResponse {
+            thread,
+            tags,
+            opening_post,
+        }))
+    }
+    .await;
+
+    match result {
+        Ok(Ok(response)) => {
+            match &user.0 {
+                Some(auth) => info!(
+                    "Thread \"{}\" added successfully by {}",
+                    body.name, auth.user.name
+                ),
+                None => info!("Thread \"{}\" added successfully", body.name),
+            }
+            Ok(HttpResponse::Created().json(response))
         }
+        Ok(Err(err)) => Err(err),
         Err(err) => {
             error!("{err}");
             Err(actix_web::error::ErrorInternalServerError(err))
@@ -37,38 +138,428 @@ async fn add(
 }
 
 #[get("")]
-async fn get(data: web::Data<SharedState>, query: web::Query<Filter>) -> Result<impl Responder> {
-    let query_string = query.prepare("SELECT * FROM threads".to_string());
-    let query_result: Vec<Thread> = match sqlx::query_as(&query_string).fetch_all(&data.db).await {
-        Ok(users) => users,
-        Err(err) => {
-            error!("{err}");
-            return Err(actix_web::error::ErrorInternalServerError(err));
+async fn get(
+    req: HttpRequest,
+    user: MaybeUser,
+    data: web::Data<SharedState>,
+    query: web::Query<Filter>,
+    list: web::Query<ThreadListQuery>,
+) -> Result<impl Responder> {
+    let mut conditions = if list.deleted {
+        if !is_staff(&user) {
+            return Err(actix_web::error::ErrorForbidden(
+                "Only moderators can list deleted threads",
+            ));
         }
+        vec!["deleted_at IS NOT NULL".to_string()]
+    } else if list.archived {
+        vec![
+            "archived_at IS NOT NULL".to_string(),
+            "deleted_at IS NULL".to_string(),
+        ]
+    } else {
+        vec![
+            "archived_at IS NULL".to_string(),
+            "deleted_at IS NULL".to_string(),
+        ]
     };
+    let tags = tag::from_query(req.query_string())?;
+    if !tags.is_empty() {
+        conditions.push(tag::condition(&tags, list.tag_match));
+    }
+    let conditions: Vec<&str> = conditions.iter().map(String::as_str).collect();
+    let query_string = query.prepare_with(select_threads(), &conditions, Some("pinned DESC"));
+    let query_result: Vec<ThreadResponse> =
+        match sqlx::query_as(&query_string).fetch_all(&data.db).await {
+            Ok(users) => users,
+            Err(err) => {
+                error!("{err}");
+                return Err(actix_web::error::ErrorInternalServerError(err));
+            }
+        };
     Ok(HttpResponse::Ok().json(query_result))
 }
 
+#[get("{id}")]
+async fn get_one(
+    path: web::Path<Uuid>,
+    user: MaybeUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    let id = path.into_inner();
+    let query_string = format!("{} WHERE threads.id = $1;", select_threads());
+    match sqlx::query_as::<_, ThreadResponse>(&query_string)
+        .bind(id)
+        .fetch_optional(&data.db)
+        .await
+    {
+        // Deleted threads only remain visible to those who can restore them
+        Ok(Some(thread)) if thread.thread.deleted_at.is_none() || is_staff(&user) => {
+            Ok(HttpResponse::Ok().json(thread))
+        }
+        Ok(_) => Err(actix_web::error::ErrorNotFound("Thread not found")),
+        Err(err) => {
+            error!("Looking up Thread {id} failed: {err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
 #[delete("")]
 async fn delete(
-    query: web::Query<IdQuery>,
+    query: web::Query<DeleteQuery>,
+    auth: AuthenticatedUser,
     data: web::Data<SharedState>,
 ) -> Result<impl Responder> {
+    auth.require(Scope::ThreadsWrite)?;
     debug!("{}", query.id);
+    let is_staff = auth.user.role().is_staff();
+
+    // Posts deleted along with the thread share its deletion time, so restoring
+    // the thread brings back exactly those
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        // Staff may remove any thread, users only their own ones holding nothing
+        // but their own posts. The lock keeps posts from coming in after the check.
+        if !is_staff {
+            let Some(thread) = sqlx::query!(
+                r#"SELECT COALESCE(author_id = $2, FALSE) AS "own!",
+                EXISTS(SELECT 1 FROM posts
+                    WHERE thread_id=$1 AND author_id IS DISTINCT FROM $2 AND deleted_at IS NULL) AS "others!"
+                FROM threads WHERE id=$1 AND deleted_at IS NULL FOR UPDATE;"#,
+                query.id,
+                auth.user.id
+            )
+            .fetch_optional(&mut *tx)
+            .await?
+            else {
+                return Ok(Ok(None));
+            };
+            if !thread.own {
+                return Ok(Err(actix_web::error::ErrorForbidden(
+                    "Can only delete your own threads",
+                )));
+            }
+            if thread.others {
+                return Ok(Err(actix_web::error::ErrorForbidden(
+                    "Thread contains posts of other users",
+                )));
+            }
+        }
+        let now = Utc::now();
+        let thread = sqlx::query_as!(
+            Thread,
+            "UPDATE threads SET deleted_at = $2, deleted_by = $3, delete_reason = $4
+            WHERE id=$1 AND deleted_at IS NULL
+            RETURNING id, name, created_at, last_active, author_id, description, pinned, locked, archived_at, board_id,
+            deleted_at, deleted_by, delete_reason;",
+            query.id,
+            now,
+            auth.user.id,
+            query.reason,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if thread.is_some() {
+            sqlx::query!(
+                "UPDATE posts SET deleted_at = $2, deleted_by = $3, delete_reason = $4
+                WHERE thread_id=$1 AND deleted_at IS NULL;",
+                query.id,
+                now,
+                auth.user.id,
+                query.reason,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Ok(thread))
+    }
+    .await;
+
+    match result {
+        Ok(Ok(Some(thread))) => {
+            info!(
+                "Thread {} deleted successfully by {}",
+                query.id, auth.user.name
+            );
+            Ok(HttpResponse::Ok().json(thread))
+        }
+        Ok(Ok(None)) => Err(actix_web::error::ErrorNotFound("Thread not found")),
+        Ok(Err(err)) => Err(err),
+        Err(err) => {
+            error!("Deleting Thread {} failed: {err}", query.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("restore")]
+async fn restore(
+    query: web::Query<IdQuery>,
+    auth: AuthenticatedUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.require(Scope::ThreadsWrite)?;
+    auth.require_role(Role::Moderator)?;
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        let Some(deleted_at) = sqlx::query_scalar!(
+            "SELECT deleted_at FROM threads WHERE id=$1 AND deleted_at IS NOT NULL FOR UPDATE;",
+            query.id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+        sqlx::query!(
+            "UPDATE posts SET deleted_at = NULL, deleted_by = NULL, delete_reason = NULL
+            WHERE thread_id=$1 AND deleted_at = $2;",
+            query.id,
+            deleted_at,
+        )
+        .execute(&mut *tx)
+        .await?;
+        let thread = sqlx::query_as!(
+            Thread,
+            "UPDATE threads SET deleted_at = NULL, deleted_by = NULL, delete_reason = NULL
+            WHERE id=$1
+            RETURNING id, name, created_at, last_active, author_id, description, pinned, locked, archived_at, board_id,
+            deleted_at, deleted_by, delete_reason;",
+            query.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some(thread))
+    }
+    .await;
+
+    match result {
+        Ok(Some(thread)) => {
+            info!("Thread {} restored by {}", query.id, auth.user.name);
+            Ok(HttpResponse::Ok().json(thread))
+        }
+        Ok(None) => Err(actix_web::error::ErrorNotFound("Deleted thread not found")),
+        Err(err) => {
+            error!("Restoring Thread {} failed: {err}", query.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("pin")]
+async fn set_pinned(
+    auth: AuthenticatedUser,
+    body: web::Json<SetPinnedRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.require(Scope::ThreadsWrite)?;
+    auth.require_role(Role::Moderator)?;
+    match sqlx::query_as!(
+        Thread,
+        "UPDATE threads SET pinned = $2 WHERE id = $1 AND deleted_at IS NULL
+        RETURNING id, name, created_at, last_active, author_id, description, pinned, locked, archived_at, board_id,
+            deleted_at, deleted_by, delete_reason;",
+        body.id,
+        body.pinned
+    )
+    .fetch_optional(&data.db)
+    .await
+    {
+        Ok(Some(thread)) => {
+            info!(
+                "Thread {} {} by {}",
+                body.id,
+                if body.pinned { "pinned" } else { "unpinned" },
+                auth.user.name
+            );
+            Ok(HttpResponse::Ok().json(thread))
+        }
+        Ok(None) => Err(actix_web::error::ErrorNotFound("Thread not found")),
+        Err(err) => {
+            error!("Pinning Thread {} failed: {err}", body.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("lock")]
+async fn set_locked(
+    auth: AuthenticatedUser,
+    body: web::Json<SetLockedRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.require(Scope::ThreadsWrite)?;
+    auth.require_role(Role::Moderator)?;
     match sqlx::query_as!(
         Thread,
-        "DELETE FROM threads WHERE id=$1 RETURNING *;",
-        query.id.clone()
+        "UPDATE threads SET locked = $2 WHERE id = $1 AND deleted_at IS NULL
+        RETURNING id, name, created_at, last_active, author_id, description, pinned, locked, archived_at, board_id,
+            deleted_at, deleted_by, delete_reason;",
+        body.id,
+        body.locked
     )
-    .fetch_one(&data.db)
+    .fetch_optional(&data.db)
     .await
     {
-        Ok(thread) => {
-            info!("Post {} deleted successfully", query.id);
+        Ok(Some(thread)) => {
+            info!(
+                "Thread {} {} by {}",
+                body.id,
+                if body.locked { "locked" } else { "unlocked" },
+                auth.user.name
+            );
             Ok(HttpResponse::Ok().json(thread))
         }
+        Ok(None) => Err(actix_web::error::ErrorNotFound("Thread not found")),
         Err(err) => {
-            error!("Deleting Post {} failed: {err}", query.id);
+            error!("Locking Thread {} failed: {err}", body.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+/// Threads with their tags and post statistics, all in one query instead of one per
+/// thread. Columns of the joined rows are aliased so filter conditions stay unambiguous.
+fn select_threads() -> String {
+    format!(
+        "SELECT threads.*,
+            ARRAY(
+                SELECT tags.name FROM thread_tags JOIN tags ON tags.id = thread_tags.tag_id
+                WHERE thread_tags.thread_id = threads.id ORDER BY tags.name
+            ) AS tags,
+            stats.post_count,
+            stats.participant_count,
+            last_post.last_post_at,
+            last_post.last_poster,
+            opening_post.preview
+        FROM threads
+        LEFT JOIN LATERAL (
+            SELECT COUNT(*) AS post_count,
+                COUNT(DISTINCT COALESCE(posts.author_id::text, posts.poster_id)) AS participant_count
+            FROM posts WHERE posts.thread_id = threads.id AND posts.deleted_at IS NULL
+        ) stats ON TRUE
+        LEFT JOIN LATERAL (
+            SELECT posts.created_at AS last_post_at,
+                CASE
+                    WHEN posts.author_id IS NULL THEN COALESCE(posts.display_name, 'Anonymous')
+                    ELSE users.name
+                END AS last_poster
+            FROM posts LEFT JOIN users ON users.id = posts.author_id
+            WHERE posts.thread_id = threads.id AND posts.deleted_at IS NULL
+            ORDER BY posts.created_at DESC LIMIT 1
+        ) last_post ON TRUE
+        LEFT JOIN LATERAL (
+            SELECT CASE
+                WHEN posts.deleted_at IS NOT NULL THEN NULL
+                WHEN char_length(posts.content) > {PREVIEW_LENGTH}
+                THEN LEFT(posts.content, {PREVIEW_LENGTH}) || '…'
+                ELSE posts.content
+            END AS preview
+            FROM posts WHERE posts.thread_id = threads.id ORDER BY posts.created_at LIMIT 1
+        ) opening_post ON TRUE"
+    )
+}
+
+fn is_staff(user: &MaybeUser) -> bool {
+    user.0
+        .as_ref()
+        .is_some_and(|auth| auth.user.role().is_staff())
+}
+
+/// Enforces the settings of the board a thread is opened on. The board is read
+/// `FOR SHARE` in the transaction inserting the thread, so it cannot change or
+/// be deleted before the thread is in.
+async fn check_board(
+    board_id: Uuid,
+    body: &AddThreadRequest,
+    user: &MaybeUser,
+    conn: &mut PgConnection,
+) -> Result<Result<()>, sqlx::Error> {
+    let Some(board) = sqlx::query_as!(
+        Board,
+        "SELECT * FROM boards WHERE id = $1 FOR SHARE;",
+        board_id
+    )
+    .fetch_optional(conn)
+    .await?
+    else {
+        return Ok(Err(actix_web::error::ErrorNotFound("Board not found")));
+    };
+    if !can_create_threads(board.min_thread_role(), user) {
+        return Ok(Err(actix_web::error::ErrorForbidden(
+            "Not allowed to create threads on this board",
+        )));
+    }
+    Ok(BoardRules {
+        allow_anonymous: Some(board.allow_anonymous),
+        max_post_length: board.max_post_length,
+    }
+    .check(&body.content, body.author_id.is_none()))
+}
+
+#[post("tags")]
+async fn set_tags(
+    auth: AuthenticatedUser,
+    body: web::Json<SetTagsRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.require(Scope::ThreadsWrite)?;
+    let tags = tag::normalize_all(&body.tags)?;
+    match sqlx::query!(
+        "SELECT author_id, archived_at FROM threads WHERE id = $1 AND deleted_at IS NULL;",
+        body.id
+    )
+    .fetch_optional(&data.db)
+    .await
+    {
+        Ok(Some(thread)) if !auth.can_moderate(thread.author_id) => {
+            return Err(actix_web::error::ErrorForbidden(
+                "Only the author or moderators can tag this thread",
+            ))
+        }
+        Ok(Some(thread)) if thread.archived_at.is_some() => {
+            return Err(actix_web::error::ErrorLocked("Thread is archived"))
+        }
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(actix_web::error::ErrorNotFound("Thread not found")),
+        Err(err) => {
+            error!("Looking up Thread {} failed: {err}", body.id);
+            return Err(actix_web::error::ErrorInternalServerError(err));
+        }
+    }
+
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        let tags = tag::set(body.id, &tags, &mut tx).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(tags)
+    }
+    .await;
+
+    match result {
+        Ok(tags) => {
+            info!(
+                "Tags of Thread {} set to {:?} by {}",
+                body.id, tags, auth.user.name
+            );
+            Ok(HttpResponse::Ok().json(tags))
+        }
+        Err(err) => {
+            error!("Tagging Thread {} failed: {err}", body.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[get("tags")]
+async fn list_tags(data: web::Data<SharedState>) -> Result<impl Responder> {
+    match tag::counts(&data.db).await {
+        Ok(tags) => Ok(HttpResponse::Ok().json(tags)),
+        Err(err) => {
+            error!("{err}");
             Err(actix_web::error::ErrorInternalServerError(err))
         }
     }
@@ -76,9 +567,17 @@ async fn delete(
 
 pub fn thread_service(conf: &mut web::ServiceConfig) {
     let scope = web::scope("api/threads")
+        .wrap(from_fn(csrf::protect))
         .service(add)
         .service(get)
-        .service(delete);
+        .service(delete)
+        .service(restore)
+        .service(set_pinned)
+        .service(set_locked)
+        .service(set_tags)
+        .service(list_tags)
+        // After the fixed paths, so those are not taken for an id
+        .service(get_one);
 
     conf.service(scope);
 }
@@ -0,0 +1,197 @@
+use actix_web::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+/// Most tags a single thread can have
+pub const MAX_TAGS: usize = 10;
+const MAX_TAG_LENGTH: usize = 32;
+
+/// Whether listed threads need any or all of the requested tags
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    #[default]
+    Any,
+    All,
+}
+
+#[derive(FromRow, Debug, Serialize, Deserialize)]
+pub struct TagCount {
+    pub name: String,
+    /// Number of threads with the tag
+    pub threads: i64,
+}
+
+/// Tags are lowercase, made of letters, digits and `-_.+` so they fit in URLs
+pub fn normalize(tag: &str) -> Result<String> {
+    let tag = tag.trim().to_lowercase();
+    let valid = (1..=MAX_TAG_LENGTH).contains(&tag.len())
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'));
+    if valid {
+        Ok(tag)
+    } else {
+        Err(actix_web::error::ErrorUnprocessableEntity(format!(
+            "Invalid tag \"{tag}\", tags have 1 to {MAX_TAG_LENGTH} letters, digits or -_.+"
+        )))
+    }
+}
+
+/// Normalizes the tags of a thread, dropping duplicates
+pub fn normalize_all(tags: &[String]) -> Result<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = normalize(tag)?;
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err(actix_web::error::ErrorUnprocessableEntity(format!(
+            "Threads can have at most {MAX_TAGS} tags"
+        )));
+    }
+    Ok(normalized)
+}
+
+/// Collects the repeated `tag` parameters of a query string, which `web::Query` cannot.
+/// Duplicates are dropped, or `TagMatch::All` would look for more tags than there are.
+pub fn from_query(query: &str) -> Result<Vec<String>> {
+    let mut tags: Vec<String> = Vec::new();
+    for (_, tag) in url::form_urlencoded::parse(query.as_bytes()).filter(|(key, _)| key == "tag") {
+        let tag = normalize(&tag)?;
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    Ok(tags)
+}
+
+/// Condition for `Filter::prepare_with` limiting threads to those with the tags,
+/// safe to inline since normalized tags cannot contain quotes
+pub fn condition(tags: &[String], mode: TagMatch) -> String {
+    let names = tags
+        .iter()
+        .map(|tag| format!("'{tag}'"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let tagged = format!(
+        "SELECT thread_tags.thread_id FROM thread_tags JOIN tags ON tags.id = thread_tags.tag_id WHERE tags.name IN ({names})"
+    );
+    match mode {
+        TagMatch::Any => format!("threads.id IN ({tagged})"),
+        TagMatch::All => format!(
+            "threads.id IN ({tagged} GROUP BY thread_tags.thread_id HAVING COUNT(*) = {})",
+            tags.len()
+        ),
+    }
+}
+
+/// Replaces the tags of a thread, creating tags which are new
+pub async fn set(
+    thread_id: Uuid,
+    tags: &[String],
+    conn: &mut PgConnection,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query!("DELETE FROM thread_tags WHERE thread_id = $1;", thread_id)
+        .execute(&mut *conn)
+        .await?;
+    for tag in tags {
+        sqlx::query!(
+            "INSERT INTO tags (id, name, created_at) VALUES($1,$2,$3) ON CONFLICT (name) DO NOTHING;",
+            Uuid::new_v4(),
+            tag,
+            Utc::now(),
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    sqlx::query!(
+        "INSERT INTO thread_tags (thread_id, tag_id) SELECT $1, id FROM tags WHERE name = ANY($2);",
+        thread_id,
+        tags,
+    )
+    .execute(&mut *conn)
+    .await?;
+    let mut tags = tags.to_vec();
+    tags.sort();
+    Ok(tags)
+}
+
+/// Tags in use with the number of threads having them, most used first. Deleted
+/// threads are left out, archived ones count since they can still be listed.
+pub async fn counts(db: &Pool<Postgres>) -> Result<Vec<TagCount>, sqlx::Error> {
+    sqlx::query_as!(
+        TagCount,
+        r#"SELECT tags.name, COUNT(*) AS "threads!" FROM tags
+        JOIN thread_tags ON thread_tags.tag_id = tags.id
+        JOIN threads ON threads.id = thread_tags.thread_id
+        WHERE threads.deleted_at IS NULL
+        GROUP BY tags.name ORDER BY 2 DESC, tags.name;"#
+    )
+    .fetch_all(db)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(" Rust ").unwrap(), "rust");
+        assert_eq!(normalize("c++").unwrap(), "c++");
+        assert_eq!(normalize("web-dev_2.0").unwrap(), "web-dev_2.0");
+        assert!(normalize("").is_err());
+        assert!(normalize("two words").is_err());
+        assert!(normalize("it's").is_err());
+        assert!(normalize(&"a".repeat(33)).is_err());
+    }
+
+    #[test]
+    fn test_normalize_all_drops_duplicates() {
+        let tags = vec!["Rust".to_string(), "rust".to_string(), "ci".to_string()];
+        assert_eq!(normalize_all(&tags).unwrap(), vec!["rust", "ci"]);
+
+        let many: Vec<String> = (0..=MAX_TAGS).map(|i| format!("tag{i}")).collect();
+        assert!(normalize_all(&many).is_err());
+    }
+
+    #[test]
+    fn test_from_query() {
+        assert_eq!(
+            from_query("tag=Rust&limit=5&tag=c%2B%2B").unwrap(),
+            vec!["rust", "c++"]
+        );
+        assert_eq!(
+            from_query("tag=rust&tag=Rust&tag=%20rust").unwrap(),
+            vec!["rust"]
+        );
+        assert!(from_query("limit=5").unwrap().is_empty());
+        assert!(from_query("tag=%27").is_err());
+    }
+
+    #[test]
+    fn test_condition() {
+        let tags = vec!["rust".to_string(), "ci".to_string()];
+        let tagged = "SELECT thread_tags.thread_id FROM thread_tags JOIN tags ON tags.id = thread_tags.tag_id WHERE tags.name IN ('rust', 'ci')";
+        assert_eq!(
+            condition(&tags, TagMatch::Any),
+            format!("threads.id IN ({tagged})")
+        );
+        assert_eq!(
+            condition(&tags, TagMatch::All),
+            format!("threads.id IN ({tagged} GROUP BY thread_tags.thread_id HAVING COUNT(*) = 2)")
+        );
+
+        // A repeated tag still needs only one match per thread
+        let tags = from_query("tag=rust&tag=ci&tag=RUST").unwrap();
+        assert_eq!(
+            condition(&tags, TagMatch::All),
+            format!("threads.id IN ({tagged} GROUP BY thread_tags.thread_id HAVING COUNT(*) = 2)")
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::{future::Future, pin::Pin};
+
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use log::error;
+use uuid::Uuid;
+
+use super::{model::User, role::Role, session, token, token::Scope};
+use crate::SharedState;
+
+/// How the user making the request proved their identity
+pub enum Credential {
+    /// Browser session from the `session_id` cookie, allowed to do everything
+    Session(Uuid),
+    /// Personal API token from the `Authorization: Bearer` header, limited to its scopes
+    ApiToken { scopes: Vec<Scope> },
+}
+
+/// Identity of the user making the request, resolved from an API token or the
+/// `session_id` cookie. Extracting it fails with 401 when neither is valid.
+pub struct AuthenticatedUser {
+    pub credential: Credential,
+    pub user: User,
+}
+
+impl AuthenticatedUser {
+    /// Fails with 403 when the request was made with a token lacking the scope
+    pub fn require(&self, scope: Scope) -> actix_web::Result<()> {
+        match &self.credential {
+            Credential::Session(_) => Ok(()),
+            Credential::ApiToken { scopes } if scopes.contains(&scope) => Ok(()),
+            Credential::ApiToken { .. } => Err(actix_web::error::ErrorForbidden(format!(
+                "API token is missing the {scope} scope"
+            ))),
+        }
+    }
+
+    /// Fails with 403 unless the user has at least the given role
+    pub fn require_role(&self, role: Role) -> actix_web::Result<()> {
+        if self.user.role() >= role {
+            Ok(())
+        } else {
+            Err(actix_web::error::ErrorForbidden(format!(
+                "Requires the {role} role"
+            )))
+        }
+    }
+
+    /// Whether the user may edit or delete content by `author_id`, which is
+    /// reserved to its author and staff. Anonymous content is staff only.
+    pub fn can_moderate(&self, author_id: Option<Uuid>) -> bool {
+        author_id == Some(self.user.id) || self.user.role().is_staff()
+    }
+
+    /// Id of the browser session, account management is not available to API tokens
+    pub fn session_id(&self) -> actix_web::Result<Uuid> {
+        match &self.credential {
+            Credential::Session(session_id) => Ok(*session_id),
+            Credential::ApiToken { .. } => Err(actix_web::error::ErrorForbidden(
+                "Not available when using an API token",
+            )),
+        }
+    }
+}
+
+/// Like [`AuthenticatedUser`], but requests without credentials are let through
+/// as anonymous instead of being rejected.
+pub struct MaybeUser(pub Option<AuthenticatedUser>);
+
+impl MaybeUser {
+    pub fn id(&self) -> Option<Uuid> {
+        self.0.as_ref().map(|auth| auth.user.id)
+    }
+}
+
+/// Value of an `Authorization: Bearer` header, if the request carries one
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+async fn resolve(req: HttpRequest) -> actix_web::Result<Option<AuthenticatedUser>> {
+    let data = req
+        .app_data::<web::Data<SharedState>>()
+        .expect("SharedState is registered as app data");
+
+    // A presented token has to be valid, falling back to anonymous would hide mistakes in bots
+    if let Some(bearer) = bearer_token(&req) {
+        return match token::find_user(bearer, &data.db).await {
+            Ok(Some((api_token, user))) => Ok(Some(AuthenticatedUser {
+                credential: Credential::ApiToken {
+                    scopes: api_token.scopes(),
+                },
+                user,
+            })),
+            Ok(None) => Err(actix_web::error::ErrorUnauthorized("Invalid API token")),
+            Err(err) => {
+                error!("Looking up API token failed: {err}");
+                Err(actix_web::error::ErrorInternalServerError(err))
+            }
+        };
+    }
+
+    let Some(token) = session::token_from_request(&req) else {
+        return Ok(None);
+    };
+    match session::find_user(&token, data.sessions.as_ref(), &data.db).await {
+        Ok(found) => Ok(found.map(|(session, user)| AuthenticatedUser {
+            credential: Credential::Session(session.id),
+            user,
+        })),
+        Err(err) => {
+            error!("Looking up session failed: {err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            resolve(req)
+                .await?
+                .ok_or_else(|| actix_web::error::ErrorUnauthorized("Invalid session"))
+        })
+    }
+}
+
+impl FromRequest for MaybeUser {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move { resolve(req).await.map(MaybeUser) })
+    }
+}
@@ -0,0 +1,173 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    cookie::{time, Cookie},
+    dev::{ServiceRequest, ServiceResponse},
+    http::Method,
+    middleware::Next,
+    HttpResponse,
+};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+use super::{auth, session};
+
+/// Cookie holding the token, readable by the frontend so it can echo it
+pub const CSRF_COOKIE: &str = "csrf_token";
+/// Header state-changing requests have to repeat the cookie in
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// Double-submit CSRF protection. Every response hands out a `csrf_token`
+/// cookie if the client has none yet, and state-changing requests carrying the
+/// session cookie have to send the same value in `X-CSRF-Token`. Other sites
+/// can make the browser send the cookie, but cannot read it to set the header.
+///
+/// Requests with a bearer token are exempt, browsers never attach those on
+/// their own, and so are requests without a session since there is nothing
+/// the browser would authenticate on their behalf.
+pub async fn protect(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let cookie_token = req
+        .cookie(CSRF_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty());
+
+    if needs_token(&req) {
+        let header_token = req
+            .headers()
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok());
+        let valid = match (&cookie_token, header_token) {
+            (Some(cookie_token), Some(header_token)) => tokens_match(cookie_token, header_token),
+            _ => false,
+        };
+        if !valid {
+            // A response instead of an error, so CORS headers still get added and the frontend can read it
+            let response = HttpResponse::Forbidden().body("Missing or invalid CSRF token");
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+
+    let mut response = next.call(req).await?;
+    if cookie_token.is_none() {
+        response.response_mut().add_cookie(&cookie(new_token()))?;
+    }
+    Ok(response.map_into_left_body())
+}
+
+fn needs_token(req: &ServiceRequest) -> bool {
+    let safe = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    !safe
+        && auth::bearer_token(req.request()).is_none()
+        && session::token_from_request(req.request()).is_some()
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Compares without returning early, so the time taken does not reveal the matching prefix
+fn tokens_match(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .bytes()
+            .zip(actual.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn cookie(token: String) -> Cookie<'static> {
+    Cookie::build(CSRF_COOKIE, token)
+        .max_age(time::Duration::days(session::SESSION_LIFETIME_DAYS))
+        .path("/")
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::{header, StatusCode},
+        middleware::from_fn,
+        test, web, App,
+    };
+
+    use super::*;
+
+    async fn ok() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    macro_rules! app {
+        () => {
+            test::init_service(
+                App::new().service(
+                    web::scope("")
+                        .wrap(from_fn(protect))
+                        .route("/", web::get().to(ok))
+                        .route("/", web::post().to(ok)),
+                ),
+            )
+            .await
+        };
+    }
+
+    #[actix_web::test]
+    async fn test_session_requests_need_matching_header() {
+        let app = app!();
+        let session = Cookie::new(session::SESSION_COOKIE, "session");
+        let csrf = Cookie::new(CSRF_COOKIE, "token");
+
+        let req = test::TestRequest::post()
+            .cookie(session.clone())
+            .cookie(csrf.clone())
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+
+        let req = test::TestRequest::post()
+            .cookie(session.clone())
+            .cookie(csrf.clone())
+            .insert_header((CSRF_HEADER, "other"))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+
+        let req = test::TestRequest::post()
+            .cookie(session)
+            .cookie(csrf)
+            .insert_header((CSRF_HEADER, "token"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_exempt_requests_and_cookie_issuing() {
+        let app = app!();
+        let session = Cookie::new(session::SESSION_COOKIE, "session");
+
+        let req = test::TestRequest::get()
+            .cookie(session.clone())
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res
+            .response()
+            .cookies()
+            .any(|cookie| cookie.name() == CSRF_COOKIE));
+
+        let req = test::TestRequest::post()
+            .cookie(session)
+            .insert_header((header::AUTHORIZATION, "Bearer token"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::post().to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+}
@@ -1,3 +1,7 @@
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -6,4 +10,45 @@ pub enum Error {
     AuthFailed,
     #[error("User account was not found.")]
     UserNotFound,
+    #[error("Password hashing failed.")]
+    Hashing,
+    #[error("Invalid two-factor code.")]
+    InvalidTwoFactorCode,
+    #[error("{0}.")]
+    InvalidUsername(String),
+    #[error("Username is already taken or too similar to an existing one.")]
+    UsernameTaken,
+    #[error("Registration is closed.")]
+    RegistrationClosed,
+    #[error("Invite code is invalid, expired or used up.")]
+    InvalidInvite,
+    #[error("Too many failed login attempts, try again in {retry_after} seconds.")]
+    TooManyAttempts { retry_after: i64 },
+    #[error("Too many secure tripcodes, try again in {retry_after} seconds.")]
+    TooManyTripcodes { retry_after: i64 },
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::AuthFailed | Error::InvalidTwoFactorCode => StatusCode::UNAUTHORIZED,
+            Error::UserNotFound => StatusCode::NOT_FOUND,
+            Error::Hashing => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::InvalidUsername(_) | Error::UsernameTaken => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::RegistrationClosed | Error::InvalidInvite => StatusCode::FORBIDDEN,
+            Error::TooManyAttempts { .. } | Error::TooManyTripcodes { .. } => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let Error::TooManyAttempts { retry_after } | Error::TooManyTripcodes { retry_after } =
+            self
+        {
+            response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+        response.body(self.to_string())
+    }
 }
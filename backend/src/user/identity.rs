@@ -0,0 +1,180 @@
+use chrono::{Duration, Utc};
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use super::model::{Identity, User};
+use super::oidc::AuthorizationRequest;
+
+/// How long the provider may take to send the browser back
+pub const LOGIN_MINUTES: i64 = 10;
+/// How long a new identity may take to pick its username
+pub const SIGNUP_MINUTES: i64 = 30;
+
+/// A login sent to the provider, everything needed to redeem its code
+pub struct PendingLogin {
+    pub code_verifier: String,
+    pub nonce: String,
+    pub link_user_id: Option<Uuid>,
+}
+
+/// An identity which logged in at the provider but has no account yet
+pub struct PendingSignup {
+    pub issuer: String,
+    pub subject: String,
+}
+
+pub async fn create_login(
+    request: &AuthorizationRequest,
+    link_user_id: Option<Uuid>,
+    db: &Pool<Postgres>,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    sqlx::query!(
+        "INSERT INTO oidc_logins (state, code_verifier, nonce, link_user_id, created_at, expires_at)
+        VALUES($1,$2,$3,$4,$5,$6);",
+        request.state,
+        request.code_verifier,
+        request.nonce,
+        link_user_id,
+        now,
+        now + Duration::minutes(LOGIN_MINUTES),
+    )
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+/// Removes the login with the given state, each callback can only be used once
+pub async fn take_login(
+    state: &str,
+    db: &Pool<Postgres>,
+) -> Result<Option<PendingLogin>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingLogin,
+        "DELETE FROM oidc_logins WHERE state = $1 AND expires_at > $2
+        RETURNING code_verifier, nonce, link_user_id;",
+        state,
+        Utc::now(),
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Returns the user an identity belongs to and remembers the login
+pub async fn find_user(
+    issuer: &str,
+    subject: &str,
+    db: &Pool<Postgres>,
+) -> Result<Option<User>, sqlx::Error> {
+    let user_id = sqlx::query_scalar!(
+        "UPDATE user_identities SET last_login_at = $3 WHERE issuer = $1 AND subject = $2
+        RETURNING user_id;",
+        issuer,
+        subject,
+        Utc::now(),
+    )
+    .fetch_optional(db)
+    .await?;
+    match user_id {
+        Some(user_id) => {
+            sqlx::query_as!(User, "SELECT * FROM users WHERE id = $1;", user_id)
+                .fetch_optional(db)
+                .await
+        }
+        None => Ok(None),
+    }
+}
+
+/// Links an identity to the user, fails with a unique violation if it is linked already
+pub async fn link(
+    user_id: Uuid,
+    issuer: &str,
+    subject: &str,
+    conn: &mut PgConnection,
+) -> Result<Identity, sqlx::Error> {
+    let now = Utc::now();
+    sqlx::query_as!(
+        Identity,
+        "INSERT INTO user_identities (id, user_id, issuer, subject, created_at, last_login_at)
+        VALUES($1,$2,$3,$4,$5,$6) RETURNING *;",
+        Uuid::new_v4(),
+        user_id,
+        issuer,
+        subject,
+        now,
+        now,
+    )
+    .fetch_one(conn)
+    .await
+}
+
+pub async fn list(user_id: Uuid, db: &Pool<Postgres>) -> Result<Vec<Identity>, sqlx::Error> {
+    sqlx::query_as!(
+        Identity,
+        "SELECT * FROM user_identities WHERE user_id = $1 ORDER BY created_at;",
+        user_id
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Unlinks an identity of the user, returns whether it existed
+pub async fn unlink(id: Uuid, user_id: Uuid, db: &Pool<Postgres>) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM user_identities WHERE id = $1 AND user_id = $2;",
+        id,
+        user_id
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Remembers an unknown identity until it picked a username, returns the signup id
+pub async fn create_signup(
+    issuer: &str,
+    subject: &str,
+    suggested_name: Option<&str>,
+    db: &Pool<Postgres>,
+) -> Result<Uuid, sqlx::Error> {
+    let now = Utc::now();
+    sqlx::query_scalar!(
+        "INSERT INTO oidc_signups (id, issuer, subject, suggested_name, created_at, expires_at)
+        VALUES($1,$2,$3,$4,$5,$6) RETURNING id;",
+        Uuid::new_v4(),
+        issuer,
+        subject,
+        suggested_name,
+        now,
+        now + Duration::minutes(SIGNUP_MINUTES),
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// Removes a pending signup, so it can only create a single account
+pub async fn take_signup(
+    id: Uuid,
+    conn: &mut PgConnection,
+) -> Result<Option<PendingSignup>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingSignup,
+        "DELETE FROM oidc_signups WHERE id = $1 AND expires_at > $2 RETURNING issuer, subject;",
+        id,
+        Utc::now(),
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Forgets logins and signups which were never finished
+pub async fn delete_expired(db: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+    let logins = sqlx::query!("DELETE FROM oidc_logins WHERE expires_at <= $1;", now)
+        .execute(db)
+        .await?;
+    let signups = sqlx::query!("DELETE FROM oidc_signups WHERE expires_at <= $1;", now)
+        .execute(db)
+        .await?;
+    Ok(logins.rows_affected() + signups.rows_affected())
+}
@@ -0,0 +1,202 @@
+use std::str::FromStr;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, Pool, Postgres};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::model::Invite;
+
+/// Most registrations a single invite of a regular user may allow
+pub const MAX_USER_INVITE_USES: i32 = 5;
+/// Longest lifetime of an invite of a regular user
+pub const MAX_USER_INVITE_HOURS: i64 = 7 * 24;
+/// Most unexpired and unexhausted invites a regular user may hold at once
+pub const MAX_USER_ACTIVE_INVITES: i64 = 3;
+
+/// Who may register new accounts, selected with `REGISTRATION_MODE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationMode {
+    /// Anybody, an invite code is optional
+    Open,
+    /// Only with a valid invite code
+    InviteOnly,
+    /// Nobody
+    Closed,
+}
+
+#[derive(Debug, Error)]
+#[error("expected \"open\", \"invite-only\" or \"closed\"")]
+pub struct InvalidRegistrationMode;
+
+impl FromStr for RegistrationMode {
+    type Err = InvalidRegistrationMode;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "open" => Ok(RegistrationMode::Open),
+            "invite-only" => Ok(RegistrationMode::InviteOnly),
+            "closed" => Ok(RegistrationMode::Closed),
+            _ => Err(InvalidRegistrationMode),
+        }
+    }
+}
+
+/// Generates a new random invite code, only ever shown to its creator once
+pub fn generate() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Codes are random, so a plain SHA-256 is enough to store them
+pub fn hash(code: &str) -> String {
+    format!(
+        "{:x}",
+        Sha256::digest(code.trim().to_lowercase().as_bytes())
+    )
+}
+
+/// Creates an invite, or returns `None` if the creator already holds
+/// `active_limit` invites which can still be used
+pub async fn create(
+    created_by: Uuid,
+    code: &str,
+    max_uses: Option<i32>,
+    expires_at: Option<DateTime<Utc>>,
+    active_limit: Option<i64>,
+    db: &Pool<Postgres>,
+) -> Result<Option<Invite>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    if let Some(limit) = active_limit {
+        // Locking the creator keeps parallel requests from all passing the count
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE;", created_by)
+            .fetch_one(&mut *tx)
+            .await?;
+        let active = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM invites
+            WHERE created_by = $1
+                AND (max_uses IS NULL OR uses < max_uses)
+                AND (expires_at IS NULL OR expires_at > $2);"#,
+            created_by,
+            Utc::now(),
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if active >= limit {
+            return Ok(None);
+        }
+    }
+    let invite = sqlx::query_as!(
+        Invite,
+        "INSERT INTO invites (id, code_hash, created_by, max_uses, created_at, expires_at)
+        VALUES($1,$2,$3,$4,$5,$6) RETURNING *;",
+        Uuid::new_v4(),
+        hash(code),
+        created_by,
+        max_uses,
+        Utc::now(),
+        expires_at,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Some(invite))
+}
+
+/// Uses up one registration of a valid code, returns the invite or `None` if the
+/// code is unknown, expired or exhausted
+pub async fn redeem(code: &str, conn: &mut PgConnection) -> Result<Option<Invite>, sqlx::Error> {
+    sqlx::query_as!(
+        Invite,
+        "UPDATE invites SET uses = uses + 1
+        WHERE code_hash = $1
+            AND (max_uses IS NULL OR uses < max_uses)
+            AND (expires_at IS NULL OR expires_at > $2)
+        RETURNING *;",
+        hash(code),
+        Utc::now(),
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Invites created by the user together with the names of everyone who used them
+pub async fn list(
+    user_id: Uuid,
+    db: &Pool<Postgres>,
+) -> Result<Vec<(Invite, Vec<String>)>, sqlx::Error> {
+    let invites = sqlx::query_as!(
+        Invite,
+        "SELECT * FROM invites WHERE created_by = $1 ORDER BY created_at DESC;",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+    let ids: Vec<Uuid> = invites.iter().map(|invite| invite.id).collect();
+    let invited = sqlx::query!(
+        "SELECT invite_id, name FROM users WHERE invite_id = ANY($1) ORDER BY created_at;",
+        &ids
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(invites
+        .into_iter()
+        .map(|invite| {
+            let names = invited
+                .iter()
+                .filter(|row| row.invite_id == Some(invite.id))
+                .map(|row| row.name.clone())
+                .collect();
+            (invite, names)
+        })
+        .collect())
+}
+
+/// Deletes an invite, `created_by` limits it to invites of that user.
+/// Returns whether anything was deleted.
+pub async fn delete(
+    invite_id: Uuid,
+    created_by: Option<Uuid>,
+    db: &Pool<Postgres>,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM invites WHERE id = $1 AND ($2::UUID IS NULL OR created_by = $2);",
+        invite_id,
+        created_by
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registration_mode_from_str() {
+        assert_eq!(
+            "open".parse::<RegistrationMode>().unwrap(),
+            RegistrationMode::Open
+        );
+        assert_eq!(
+            "invite-only".parse::<RegistrationMode>().unwrap(),
+            RegistrationMode::InviteOnly
+        );
+        assert_eq!(
+            "closed".parse::<RegistrationMode>().unwrap(),
+            RegistrationMode::Closed
+        );
+        assert!("invite".parse::<RegistrationMode>().is_err());
+    }
+
+    #[test]
+    fn test_hash_ignores_case_and_whitespace() {
+        let code = generate();
+        assert_eq!(code.len(), 32);
+        assert_eq!(hash(&code), hash(&format!(" {} ", code.to_uppercase())));
+    }
+}
@@ -0,0 +1,151 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{Pool, Postgres};
+
+/// Failures older than this are forgotten
+const FAILURE_MEMORY_HOURS: i64 = 24;
+/// Upper bound for a single lockout
+const MAX_LOCKOUT_HOURS: i64 = 24;
+
+/// Limits on failed logins, see `Config` for the defaults
+#[derive(Clone, Debug)]
+pub struct LockoutPolicy {
+    /// Failures after which the subject gets locked out
+    pub max_failures: i32,
+    /// Length of the first lockout, doubled for every further failure
+    pub lockout: Duration,
+}
+
+/// Subject for failures of a single account
+pub fn user_subject(username: &str) -> String {
+    format!("user:{username}")
+}
+
+/// Subject for failures coming from a single client
+pub fn client_subject(address: &str) -> String {
+    format!("client:{address}")
+}
+
+/// How long a subject has to wait after its `failures`-th failed attempt.
+/// Below the threshold the delay grows from one second, at the threshold
+/// the lockout starts and keeps doubling.
+pub fn delay_after(failures: i32, policy: &LockoutPolicy) -> Duration {
+    let delay = if failures < policy.max_failures {
+        Duration::seconds(1 << (failures - 1).clamp(0, 30))
+    } else {
+        let exponent = (failures - policy.max_failures).clamp(0, 30) as u32;
+        policy
+            .lockout
+            .checked_mul(2i32.saturating_pow(exponent))
+            .unwrap_or(Duration::hours(MAX_LOCKOUT_HOURS))
+    };
+    delay.min(Duration::hours(MAX_LOCKOUT_HOURS))
+}
+
+/// Returns the number of seconds until the longest running lock of the subjects ends
+pub async fn retry_after(
+    subjects: &[String],
+    db: &Pool<Postgres>,
+) -> Result<Option<i64>, sqlx::Error> {
+    let now = Utc::now();
+    let locked_until: Option<DateTime<Utc>> = sqlx::query_scalar!(
+        "SELECT MAX(locked_until) FROM login_failures WHERE subject = ANY($1) AND locked_until > $2;",
+        subjects,
+        now
+    )
+    .fetch_one(db)
+    .await?;
+    // Round up so clients never retry a moment too early
+    Ok(locked_until.map(|until| ((until - now).num_milliseconds() + 999) / 1000))
+}
+
+pub async fn record_failure(
+    subjects: &[String],
+    policy: &LockoutPolicy,
+    db: &Pool<Postgres>,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    let forget_before = now - Duration::hours(FAILURE_MEMORY_HOURS);
+    let mut tx = db.begin().await?;
+    for subject in subjects {
+        let previous = sqlx::query_scalar!(
+            "SELECT failures FROM login_failures WHERE subject = $1 AND last_failure_at > $2 FOR UPDATE;",
+            subject,
+            forget_before
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .unwrap_or(0);
+        let failures = previous.saturating_add(1);
+        sqlx::query!(
+            "INSERT INTO login_failures (subject, failures, last_failure_at, locked_until)
+            VALUES($1,$2,$3,$4)
+            ON CONFLICT (subject) DO UPDATE
+            SET failures = $2, last_failure_at = $3, locked_until = $4;",
+            subject,
+            failures,
+            now,
+            now + delay_after(failures, policy),
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+pub async fn reset(subject: &str, db: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM login_failures WHERE subject = $1;", subject)
+        .execute(db)
+        .await
+        .map(|_| ())
+}
+
+/// Removes failures which are forgotten and no longer lock anyone out
+pub async fn delete_stale(db: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+    sqlx::query!(
+        "DELETE FROM login_failures WHERE last_failure_at <= $1 AND locked_until <= $2;",
+        now - Duration::hours(FAILURE_MEMORY_HOURS),
+        now
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> LockoutPolicy {
+        LockoutPolicy {
+            max_failures: 5,
+            lockout: Duration::minutes(15),
+        }
+    }
+
+    #[test]
+    fn test_backoff_below_threshold() {
+        assert_eq!(delay_after(1, &policy()), Duration::seconds(1));
+        assert_eq!(delay_after(2, &policy()), Duration::seconds(2));
+        assert_eq!(delay_after(4, &policy()), Duration::seconds(8));
+    }
+
+    #[test]
+    fn test_lockout_doubles_after_threshold() {
+        assert_eq!(delay_after(5, &policy()), Duration::minutes(15));
+        assert_eq!(delay_after(6, &policy()), Duration::minutes(30));
+        assert_eq!(delay_after(7, &policy()), Duration::minutes(60));
+    }
+
+    #[test]
+    fn test_lockout_is_capped() {
+        assert_eq!(
+            delay_after(40, &policy()),
+            Duration::hours(MAX_LOCKOUT_HOURS)
+        );
+        assert_eq!(
+            delay_after(i32::MAX, &policy()),
+            Duration::hours(MAX_LOCKOUT_HOURS)
+        );
+    }
+}
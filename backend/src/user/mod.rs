@@ -1,4 +1,17 @@
+pub(crate) mod auth;
+pub(crate) mod csrf;
 pub(crate) mod error;
+pub(crate) mod identity;
+pub(crate) mod invite;
+pub(crate) mod lockout;
 pub(crate) mod model;
+pub(crate) mod oidc;
+pub(crate) mod password;
+pub(crate) mod recovery;
+pub(crate) mod role;
 pub(crate) mod schema;
 pub mod service;
+pub(crate) mod session;
+pub(crate) mod token;
+pub(crate) mod two_factor;
+pub(crate) mod username;
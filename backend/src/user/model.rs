@@ -3,7 +3,11 @@ use serde::{Deserialize, Serialize};
 use sqlx::{types::chrono::Utc, FromRow};
 use uuid::Uuid;
 
-use super::schema::UserResponse;
+use super::role::Role;
+use super::schema::{
+    ApiTokenResponse, IdentityResponse, InviteResponse, SessionResponse, UserResponse,
+};
+use super::token::Scope;
 
 #[derive(FromRow, Debug, Serialize, Deserialize)]
 pub struct User {
@@ -12,7 +16,14 @@ pub struct User {
     pub password_hash: String,
     pub created_at: DateTime<Utc>,
     pub last_active: DateTime<Utc>,
-    pub salt: String,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub name_key: Option<String>,
+    pub role: String,
+    pub session_generation: i32,
+    pub invited_by: Option<Uuid>,
+    pub invite_id: Option<Uuid>,
+    pub totp_last_step: Option<i64>,
 }
 
 impl User {
@@ -22,6 +33,113 @@ impl User {
             username: self.name.clone(),
             created_at: self.created_at,
             last_active: self.last_active,
+            two_factor_enabled: self.totp_enabled,
+            role: self.role(),
+        }
+    }
+
+    /// Roles are constrained by the database, anything unknown gets no extra permissions
+    pub fn role(&self) -> Role {
+        Role::parse(&self.role).unwrap_or(Role::User)
+    }
+}
+
+#[derive(FromRow, Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+}
+
+impl Session {
+    pub fn as_response(&self, current: bool) -> SessionResponse {
+        SessionResponse {
+            id: self.id,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            user_agent: self.user_agent.clone(),
+            current,
+        }
+    }
+}
+
+#[derive(FromRow, Debug, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    /// Scopes stored with the token, unknown ones are ignored
+    pub fn scopes(&self) -> Vec<Scope> {
+        self.scopes
+            .iter()
+            .filter_map(|scope| Scope::parse(scope))
+            .collect()
+    }
+
+    pub fn as_response(&self, token: Option<String>) -> ApiTokenResponse {
+        ApiTokenResponse {
+            id: self.id,
+            name: self.name.clone(),
+            scopes: self.scopes(),
+            created_at: self.created_at,
+            last_used_at: self.last_used_at,
+            token,
+        }
+    }
+}
+
+#[derive(FromRow, Debug, Serialize, Deserialize)]
+pub struct Invite {
+    pub id: Uuid,
+    pub code_hash: String,
+    pub created_by: Option<Uuid>,
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Invite {
+    pub fn as_response(&self, code: Option<String>, invited: Vec<String>) -> InviteResponse {
+        InviteResponse {
+            id: self.id,
+            max_uses: self.max_uses,
+            uses: self.uses,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            invited,
+            code,
+        }
+    }
+}
+
+#[derive(FromRow, Debug, Serialize, Deserialize)]
+pub struct Identity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub issuer: String,
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: DateTime<Utc>,
+}
+
+impl Identity {
+    pub fn as_response(&self) -> IdentityResponse {
+        IdentityResponse {
+            id: self.id,
+            issuer: self.issuer.clone(),
+            subject: self.subject.clone(),
+            created_at: self.created_at,
+            last_login_at: self.last_login_at,
         }
     }
 }
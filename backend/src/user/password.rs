@@ -0,0 +1,100 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+
+use super::error::Error;
+
+/// Result of a successful password check
+#[derive(Debug, PartialEq)]
+pub enum Verified {
+    /// The stored hash matches the current parameters
+    Current,
+    /// The stored hash uses outdated parameters and should be replaced
+    NeedsRehash,
+}
+
+fn hasher(params: &Params) -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+}
+
+/// Hashes the password into a PHC string, the salt is embedded in the result
+pub fn hash(password: &str, params: &Params) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    hasher(params)
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|_| Error::Hashing)
+}
+
+/// Verifies the password against a stored PHC string using the parameters embedded in it
+pub fn verify(password: &str, phc: &str, params: &Params) -> Result<Verified, Error> {
+    let hash = PasswordHash::new(phc).map_err(|_| Error::Hashing)?;
+    hasher(params)
+        .verify_password(password.as_bytes(), &hash)
+        .map_err(|_| Error::AuthFailed)?;
+    if is_outdated(&hash, params) {
+        Ok(Verified::NeedsRehash)
+    } else {
+        Ok(Verified::Current)
+    }
+}
+
+fn is_outdated(hash: &PasswordHash, params: &Params) -> bool {
+    if hash.algorithm != Algorithm::Argon2id.ident() || hash.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+    match Params::try_from(hash) {
+        Ok(stored) => {
+            stored.m_cost() != params.m_cost()
+                || stored.t_cost() != params.t_cost()
+                || stored.p_cost() != params.p_cost()
+        }
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(t_cost: u32) -> Params {
+        Params::new(Params::MIN_M_COST * 2, t_cost, 1, None).unwrap()
+    }
+
+    #[test]
+    fn test_verify_correct_password() {
+        let phc = hash("hunter2", &params(1)).unwrap();
+        assert_eq!(
+            verify("hunter2", &phc, &params(1)).unwrap(),
+            Verified::Current
+        );
+    }
+
+    #[test]
+    fn test_verify_wrong_password() {
+        let phc = hash("hunter2", &params(1)).unwrap();
+        assert!(matches!(
+            verify("hunter3", &phc, &params(1)),
+            Err(Error::AuthFailed)
+        ));
+    }
+
+    #[test]
+    fn test_verify_outdated_params() {
+        let phc = hash("hunter2", &params(1)).unwrap();
+        assert_eq!(
+            verify("hunter2", &phc, &params(2)).unwrap(),
+            Verified::NeedsRehash
+        );
+    }
+
+    #[test]
+    fn test_verify_invalid_hash() {
+        assert!(matches!(
+            verify("hunter2", "not a phc string", &params(1)),
+            Err(Error::Hashing)
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+use chrono::Utc;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use super::two_factor::hash_recovery_code;
+
+/// Replaces all account recovery codes of the user with the given ones
+pub async fn store_codes(
+    user_id: Uuid,
+    codes: &[String],
+    conn: &mut PgConnection,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM account_recovery_codes WHERE user_id = $1;",
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    for code in codes {
+        sqlx::query!(
+            "INSERT INTO account_recovery_codes (id, user_id, code_hash) VALUES($1,$2,$3);",
+            Uuid::new_v4(),
+            user_id,
+            hash_recovery_code(code),
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Marks a recovery code as used, returns whether it was valid and unused
+pub async fn use_code(
+    user_id: Uuid,
+    code: &str,
+    conn: &mut PgConnection,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "UPDATE account_recovery_codes SET used_at = $1
+        WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL;",
+        Utc::now(),
+        user_id,
+        hash_recovery_code(code),
+    )
+    .execute(conn)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Number of recovery codes the user has left
+pub async fn remaining(user_id: Uuid, conn: &mut PgConnection) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM account_recovery_codes WHERE user_id = $1 AND used_at IS NULL;",
+        user_id
+    )
+    .fetch_one(conn)
+    .await
+    .map(|count| count.unwrap_or(0))
+}
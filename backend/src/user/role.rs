@@ -0,0 +1,107 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use super::model::User;
+
+/// Role of a user, later variants include all permissions of earlier ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "user" => Some(Role::User),
+            "moderator" => Some(Role::Moderator),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+
+    /// Staff may moderate threads and posts of other users
+    pub fn is_staff(&self) -> bool {
+        *self >= Role::Moderator
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Changes the role of a user, returns the updated user or `None` if it does not exist
+pub async fn set(
+    user_id: Uuid,
+    role: Role,
+    db: &Pool<Postgres>,
+) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        "UPDATE users SET role = $1 WHERE id = $2 RETURNING *;",
+        role.as_str(),
+        user_id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Makes the user with the given name key an admin, as long as there is no admin yet.
+/// Returns the promoted user.
+pub async fn bootstrap_admin(
+    name_key: &str,
+    db: &Pool<Postgres>,
+) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        "UPDATE users SET role = 'admin'
+        WHERE name_key = $1 AND NOT EXISTS (SELECT 1 FROM users WHERE role = 'admin')
+        RETURNING *;",
+        name_key
+    )
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn admin_exists(db: &Pool<Postgres>) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM users WHERE role = 'admin');")
+        .fetch_one(db)
+        .await
+        .map(|exists| exists.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_round_trip() {
+        for role in [Role::User, Role::Moderator, Role::Admin] {
+            assert_eq!(Role::parse(role.as_str()), Some(role));
+        }
+        assert_eq!(Role::parse("root"), None);
+    }
+
+    #[test]
+    fn test_staff_roles() {
+        assert!(!Role::User.is_staff());
+        assert!(Role::Moderator.is_staff());
+        assert!(Role::Admin.is_staff());
+        assert!(Role::Admin > Role::Moderator);
+    }
+}
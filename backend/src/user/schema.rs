@@ -2,15 +2,28 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use super::role::Role;
+use super::token::Scope;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserAuthRequest {
     pub name: String,
     pub password: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterRequest {
+    pub name: String,
+    pub password: String,
+    /// Required when registration is invite-only
+    #[serde(default)]
+    pub invite_code: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SessionAuthRequest {
-    pub session_id: Uuid,
+    /// Value of the `session_id` cookie
+    pub session_id: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,10 +32,174 @@ pub struct UserResponse {
     pub username: String,
     pub created_at: DateTime<Utc>,
     pub last_active: DateTime<Utc>,
+    pub two_factor_enabled: bool,
+    pub role: Role,
+}
+
+/// Returned by login instead of a session when the account has 2FA enabled
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorChallengeResponse {
+    pub two_factor_token: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorLoginRequest {
+    /// Taken from the `two_factor_token` cookie when missing, which single sign-on sets
+    #[serde(default)]
+    pub two_factor_token: Option<Uuid>,
+    /// Code from the authenticator or one of the recovery codes
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorEnrollResponse {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorConfirmRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Returned by registration, the only time the account recovery codes are shown
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterResponse {
+    #[serde(flatten)]
+    pub user: UserResponse,
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoverAccountRequest {
+    pub name: String,
+    /// One of the account recovery codes handed out at registration
+    pub recovery_code: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoverAccountResponse {
+    pub remaining_recovery_codes: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewRecoveryCodesRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorDisableRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeleteUserRequest {
+    pub password: String,
+    /// Delete the user's posts instead of keeping them as anonymous
+    #[serde(default)]
+    pub delete_posts: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiTokenResponse {
+    pub id: Uuid,
     pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// The token itself, only present right after it was created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetRoleRequest {
+    pub user_id: Uuid,
+    pub role: Role,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateInviteRequest {
+    /// How many accounts can be registered with the code, unlimited if missing
+    pub max_uses: Option<i32>,
+    /// Hours until the code expires, never if missing
+    pub expires_in_hours: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InviteResponse {
+    pub id: Uuid,
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Names of the users who registered with the code
+    pub invited: Vec<String>,
+    /// The code itself, only present right after it was created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcLoginQuery {
+    /// Link the identity to the logged in user instead of logging in with it
+    #[serde(default)]
+    pub link: bool,
+}
+
+/// Parameters the provider appends when sending the browser back
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcCallbackQuery {
+    pub state: String,
+    pub code: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcSignupRequest {
+    /// The signup itself comes from the `oidc_signup` cookie the callback sets
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdentityResponse {
+    pub id: Uuid,
+    pub issuer: String,
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnlinkIdentityRequest {
+    pub id: Uuid,
+    /// Proves the account can still be logged into without the identity
     pub password: String,
 }
@@ -1,57 +1,126 @@
-use actix_web::{cookie::time::Duration, get, post, web, HttpResponse, Responder, Result};
-use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
-    Argon2,
+use actix_web::middleware::from_fn;
+use actix_web::{
+    delete, get, http::header, post, web, HttpRequest, HttpResponse, Responder, Result,
 };
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use log::{debug, error, info};
-use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 
-use crate::user::schema::{SessionAuthRequest, UserAuthRequest};
+use crate::common::client;
+use crate::common::id::IdQuery;
+use crate::user::auth::{AuthenticatedUser, MaybeUser};
+use crate::user::invite::{self, RegistrationMode};
+use crate::user::model::Identity;
+use crate::user::oidc::{self, OidcClient};
+use crate::user::password::{self, Verified};
+use crate::user::role::Role;
+use crate::user::schema::{
+    ApiTokenResponse, ChangePasswordRequest, CreateApiTokenRequest, CreateInviteRequest,
+    DeleteUserRequest, IdentityResponse, InviteResponse, NewRecoveryCodesRequest,
+    OidcCallbackQuery, OidcLoginQuery, OidcSignupRequest, RecoverAccountRequest,
+    RecoverAccountResponse, RecoveryCodesResponse, RegisterRequest, RegisterResponse,
+    SessionAuthRequest, SessionResponse, SetRoleRequest, TwoFactorChallengeResponse,
+    TwoFactorConfirmRequest, TwoFactorDisableRequest, TwoFactorEnrollResponse,
+    TwoFactorLoginRequest, UnlinkIdentityRequest, UserAuthRequest, UserResponse,
+};
+use crate::user::token::{self, Scope};
+use crate::user::{csrf, identity, lockout, recovery, role, session, two_factor, username};
 use crate::{user::model::User, SharedState};
 
 use super::error::Error;
 
 #[post("register")]
 async fn register_user(
-    body: web::Json<UserAuthRequest>,
+    req: HttpRequest,
+    body: web::Json<RegisterRequest>,
     data: web::Data<SharedState>,
 ) -> Result<impl Responder> {
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(body.password.as_bytes(), &salt)
-        .expect("Failed hashing")
-        .to_string();
+    let name = username::normalize(&body.name)?;
+    let name_key = username::key(&name);
 
-    let salt_str = salt.to_string();
+    // The configured first admin may always sign up, a closed instance would never get one otherwise
+    let bypass_mode = is_bootstrap_admin(&name_key, &data)
+        && match role::admin_exists(&data.db).await {
+            Ok(exists) => !exists,
+            Err(err) => {
+                error!("Checking for an admin failed: {err}");
+                return Err(actix_web::error::ErrorInternalServerError(err));
+            }
+        };
+    if !bypass_mode {
+        match data.config.registration_mode {
+            RegistrationMode::Open => {}
+            RegistrationMode::InviteOnly if body.invite_code.is_some() => {}
+            RegistrationMode::InviteOnly => return Err(Error::InvalidInvite.into()),
+            RegistrationMode::Closed => return Err(Error::RegistrationClosed.into()),
+        }
+    }
 
-    match sqlx::query_as!(
-        User,
-        "INSERT INTO users VALUES($1,$2,$3,$4,$5,$6) RETURNING *;",
-        Uuid::new_v4(),
-        body.name,
-        password_hash,
-        Utc::now(),
-        Utc::now(),
-        salt_str
-    )
-    .fetch_one(&data.db)
-    .await
-    {
-        Ok(user) => {
-            info!("User {} registered successfully", body.name);
-            let session_id = uuid::Uuid::new_v4();
-            let session_id_string = session_id.to_string();
-            let cookie = actix_web::cookie::Cookie::build("session_id", session_id_string)
-                .max_age(Duration::days(3))
-                .path("/")
-                .finish();
-            data.user_sessions.lock().await.insert(session_id, user.id);
+    check_name_available(&name, &name_key, &data).await?;
+
+    let password_hash = password::hash(&body.password, &data.config.argon2).map_err(|err| {
+        error!("Hashing password of user {} failed", name);
+        actix_web::error::ErrorInternalServerError(err)
+    })?;
+
+    // Recovery codes are the only way back into an account with a forgotten password
+    let recovery_codes = two_factor::generate_recovery_codes();
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        // A given code has to be valid even where registration is open
+        let invite = match &body.invite_code {
+            Some(code) => match invite::redeem(code, &mut tx).await? {
+                Some(invite) => Some(invite),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+        let user = sqlx::query_as!(
+            User,
+            "INSERT INTO users (id, name, password_hash, created_at, last_active, name_key, invited_by, invite_id)
+            VALUES($1,$2,$3,$4,$5,$6,$7,$8) RETURNING *;",
+            Uuid::new_v4(),
+            name,
+            password_hash,
+            Utc::now(),
+            Utc::now(),
+            name_key,
+            invite.as_ref().and_then(|invite| invite.created_by),
+            invite.as_ref().map(|invite| invite.id),
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        recovery::store_codes(user.id, &recovery_codes, &mut tx).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some(user))
+    }
+    .await;
+
+    match result {
+        Ok(None) => Err(Error::InvalidInvite.into()),
+        Ok(Some(user)) => {
+            info!("User {} registered successfully", name);
+            let user = promote_bootstrap_admin(user, &data).await;
+            let (_, token) = session::create(user.id, &req, data.sessions.as_ref())
+                .await
+                .map_err(|err| {
+                    error!("Creating session for user {} failed: {err}", user.id);
+                    actix_web::error::ErrorInternalServerError(err)
+                })?;
             Ok(HttpResponse::Created()
-                .cookie(cookie)
-                .json(user.as_reponse()))
+                .cookie(session::cookie(token))
+                .json(RegisterResponse {
+                    user: user.as_reponse(),
+                    recovery_codes,
+                }))
+        }
+        // Lost a race against another registration of the same name
+        Err(err)
+            if err
+                .as_database_error()
+                .is_some_and(|err| err.is_unique_violation()) =>
+        {
+            Err(Error::UsernameTaken.into())
         }
         Err(err) => {
             error!("{err}");
@@ -62,32 +131,101 @@ async fn register_user(
 
 #[post("login")]
 async fn login_user(
+    req: HttpRequest,
     body: web::Json<UserAuthRequest>,
     data: web::Data<SharedState>,
 ) -> Result<impl Responder> {
-    match auth_user(body.name.clone(), body.password.clone(), data.db.clone()).await {
-        Ok(user) => {
-            let session_id = uuid::Uuid::new_v4();
-            let session_id_string = session_id.to_string();
-            let cookie = actix_web::cookie::Cookie::build("session_id", session_id_string)
-                .max_age(Duration::days(3))
-                .path("/")
-                .finish();
-            data.user_sessions.lock().await.insert(session_id, user.id);
+    let user_subject = lockout::user_subject(&username::key(&body.name));
+    let subjects = [
+        user_subject.clone(),
+        lockout::client_subject(&client::address(&req, &data.config)),
+    ];
+    check_lockout(&subjects, &body.name, &data).await?;
+
+    let result = auth_user(&body.name, &body.password, &data).await;
+    let recorded = match &result {
+        Ok(_) => lockout::reset(&user_subject, &data.db).await,
+        Err(Error::AuthFailed | Error::UserNotFound) => {
+            lockout::record_failure(&subjects, &data.config.login_lockout, &data.db).await
+        }
+        Err(_) => Ok(()),
+    };
+    if let Err(err) = recorded {
+        error!("Updating login failures of {} failed: {err}", body.name);
+    }
 
-            Ok(HttpResponse::Ok().cookie(cookie).json(user.as_reponse()))
+    match result {
+        Ok(user) if user.totp_enabled => {
+            // The session is only issued after the second step in `login_two_factor`
+            let token = two_factor::create_pending_login(user.id, &data.db)
+                .await
+                .map_err(|err| {
+                    error!("Creating pending login for user {} failed: {err}", user.id);
+                    actix_web::error::ErrorInternalServerError(err)
+                })?;
+            Ok(HttpResponse::Accepted().json(TwoFactorChallengeResponse {
+                two_factor_token: token,
+            }))
         }
+        Ok(user) => start_session(&user, &req, &data).await,
         Err(e) => Err(actix_web::error::ErrorUnauthorized(e)),
     }
 }
 
+#[post("login/2fa")]
+async fn login_two_factor(
+    req: HttpRequest,
+    body: web::Json<TwoFactorLoginRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    let Some(token) = body
+        .two_factor_token
+        .or_else(|| two_factor::pending_login_from_request(&req))
+    else {
+        return Err(actix_web::error::ErrorUnauthorized(
+            "Login expired, please log in again",
+        ));
+    };
+    let user = match two_factor::claim_attempt(token, &data.db).await {
+        Ok(Some(user_id)) => {
+            sqlx::query_as!(User, "SELECT * FROM users WHERE id = $1;", user_id)
+                .fetch_one(&data.db)
+                .await
+        }
+        Ok(None) => {
+            return Err(actix_web::error::ErrorUnauthorized(
+                "Login expired, please log in again",
+            ))
+        }
+        Err(err) => Err(err),
+    }
+    .map_err(|err| {
+        error!("Looking up pending login {token} failed: {err}");
+        actix_web::error::ErrorInternalServerError(err)
+    })?;
+
+    if !check_second_factor(&user, &body.code, &data).await? {
+        return Err(actix_web::error::ErrorUnauthorized(
+            Error::InvalidTwoFactorCode,
+        ));
+    }
+    if let Err(err) = two_factor::delete_pending_login(token, &data.db).await {
+        error!("Deleting pending login {token} failed: {err}");
+    }
+    let mut response = start_session(&user, &req, &data).await?;
+    response
+        .add_cookie(&two_factor::pending_login_removal_cookie())
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(response)
+}
+
 #[get("")]
 async fn get_users(data: web::Data<SharedState>) -> Result<impl Responder> {
-    let query_result = match sqlx::query_as!(User, "SELECT * FROM users")
+    let query_result: Vec<UserResponse> = match sqlx::query_as!(User, "SELECT * FROM users")
         .fetch_all(&data.db)
         .await
     {
-        Ok(users) => users,
+        Ok(users) => users.iter().map(User::as_reponse).collect(),
         Err(err) => {
             error!("{err}");
             Vec::new()
@@ -101,55 +239,1184 @@ async fn auth_session(
     body: web::Json<SessionAuthRequest>,
     data: web::Data<SharedState>,
 ) -> Result<impl Responder> {
-    debug!("Sessions{:?}", data.user_sessions.lock().await);
-    if let Some(user_id) = data.user_sessions.lock().await.get(&body.session_id) {
-        match sqlx::query_as!(User, "SELECT * FROM users WHERE(id = $1);", user_id)
-            .fetch_one(&data.db)
-            .await
-        {
-            Ok(user) => Ok(HttpResponse::Ok().json(user.as_reponse())),
-            Err(_) => {
-                error!("User with id {} does not exist", user_id);
-                Err(actix_web::error::ErrorNotFound("Invalid session"))
+    match session::find_user(&body.session_id, data.sessions.as_ref(), &data.db).await {
+        Ok(Some((_, user))) => Ok(HttpResponse::Ok().json(user.as_reponse())),
+        Ok(None) => Err(actix_web::error::ErrorNotFound("Invalid session")),
+        Err(err) => {
+            error!("Looking up session failed: {err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("logout")]
+async fn logout_user(
+    auth: AuthenticatedUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    let session_id = auth.session_id()?;
+    let user = auth.user;
+    match data.sessions.delete(session_id, user.id).await {
+        Ok(_) => {
+            info!("User {} logged out", user.name);
+            Ok(HttpResponse::Ok()
+                .cookie(session::removal_cookie())
+                .finish())
+        }
+        Err(err) => {
+            error!("Deleting session {session_id} failed: {err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[get("sessions")]
+async fn get_sessions(
+    auth: AuthenticatedUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    let session_id = auth.session_id()?;
+    let user = auth.user;
+    match data.sessions.list_active(user.id).await {
+        Ok(sessions) => {
+            let response: Vec<SessionResponse> = sessions
+                .iter()
+                .map(|session| session.as_response(session.id == session_id))
+                .collect();
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(err) => {
+            error!("Listing sessions of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[delete("sessions")]
+async fn revoke_session(
+    auth: AuthenticatedUser,
+    query: web::Query<IdQuery>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    let session_id = auth.session_id()?;
+    let user = auth.user;
+    match data.sessions.delete(query.id, user.id).await {
+        Ok(true) => {
+            info!("Session {} of user {} revoked", query.id, user.name);
+            let mut response = HttpResponse::Ok();
+            if query.id == session_id {
+                response.cookie(session::removal_cookie());
             }
+            Ok(response.finish())
+        }
+        Ok(false) => Err(actix_web::error::ErrorNotFound("Session not found")),
+        Err(err) => {
+            error!("Revoking session {} failed: {err}", query.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[delete("sessions/all")]
+async fn revoke_all_sessions(
+    auth: AuthenticatedUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    match data.sessions.delete_all(user.id).await {
+        Ok(count) => {
+            info!("Revoked {count} sessions of user {}", user.name);
+            Ok(HttpResponse::Ok()
+                .cookie(session::removal_cookie())
+                .finish())
+        }
+        Err(err) => {
+            error!("Revoking sessions of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("password")]
+async fn change_password(
+    req: HttpRequest,
+    auth: AuthenticatedUser,
+    body: web::Json<ChangePasswordRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    password::verify(
+        &body.current_password,
+        &user.password_hash,
+        &data.config.argon2,
+    )
+    .map_err(actix_web::error::ErrorUnauthorized)?;
+    let password_hash = password::hash(&body.new_password, &data.config.argon2).map_err(|err| {
+        error!("Hashing password of user {} failed", user.id);
+        actix_web::error::ErrorInternalServerError(err)
+    })?;
+
+    // Every session is ended together with the change, the one making it is
+    // replaced by a fresh one afterwards
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        sqlx::query!(
+            "UPDATE users SET password_hash = $1 WHERE id = $2;",
+            password_hash,
+            user.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        data.sessions.delete_all_in(user.id, &mut tx).await?;
+        tx.commit().await?;
+        session::create(user.id, &req, data.sessions.as_ref()).await
+    }
+    .await;
+
+    match result {
+        Ok((_, token)) => {
+            info!("User {} changed password", user.name);
+            Ok(HttpResponse::Ok().cookie(session::cookie(token)).finish())
+        }
+        Err(err) => {
+            error!("Changing password of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[delete("me")]
+async fn delete_user(
+    auth: AuthenticatedUser,
+    body: web::Json<DeleteUserRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    password::verify(&body.password, &user.password_hash, &data.config.argon2)
+        .map_err(actix_web::error::ErrorUnauthorized)?;
+
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        if body.delete_posts {
+            sqlx::query!("DELETE FROM posts WHERE author_id = $1;", user.id)
+                .execute(&mut *tx)
+                .await?;
+        } else {
+            sqlx::query!(
+                "UPDATE posts SET author_id = NULL WHERE author_id = $1;",
+                user.id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query!("DELETE FROM users WHERE id = $1;", user.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        // Database sessions are removed together with the user, other stores need to be told
+        data.sessions.delete_all(user.id).await
+    }
+    .await;
+
+    match result {
+        Ok(_) => {
+            info!("User {} deleted their account", user.name);
+            Ok(HttpResponse::Ok()
+                .cookie(session::removal_cookie())
+                .finish())
+        }
+        Err(err) => {
+            error!("Deleting user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("2fa/enroll")]
+async fn enroll_two_factor(
+    auth: AuthenticatedUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    if user.totp_enabled {
+        return Err(actix_web::error::ErrorConflict(
+            "Two-factor authentication is already enabled",
+        ));
+    }
+    let secret = two_factor::new_secret();
+    let provisioning_uri = two_factor::provisioning_uri(&secret, &user.name)
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    match sqlx::query!(
+        "UPDATE users SET totp_secret = $1 WHERE id = $2;",
+        secret,
+        user.id
+    )
+    .execute(&data.db)
+    .await
+    {
+        Ok(_) => Ok(HttpResponse::Ok().json(TwoFactorEnrollResponse {
+            secret,
+            provisioning_uri,
+        })),
+        Err(err) => {
+            error!("Storing TOTP secret of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("2fa/confirm")]
+async fn confirm_two_factor(
+    auth: AuthenticatedUser,
+    body: web::Json<TwoFactorConfirmRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    let secret = match (&user.totp_secret, user.totp_enabled) {
+        (Some(secret), false) => secret,
+        (_, true) => {
+            return Err(actix_web::error::ErrorConflict(
+                "Two-factor authentication is already enabled",
+            ))
+        }
+        (None, false) => {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Two-factor enrollment was not started",
+            ))
+        }
+    };
+    let step = two_factor::verify_code(secret, &user.name, &body.code)
+        .map_err(actix_web::error::ErrorUnauthorized)?;
+    if !use_time_step(&user, step, &data).await? {
+        return Err(actix_web::error::ErrorUnauthorized(
+            Error::InvalidTwoFactorCode,
+        ));
+    }
+
+    let recovery_codes = two_factor::generate_recovery_codes();
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        two_factor::store_recovery_codes(user.id, &recovery_codes, &mut tx).await?;
+        sqlx::query!(
+            "UPDATE users SET totp_enabled = TRUE WHERE id = $1;",
+            user.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+    .await;
+    match result {
+        Ok(_) => {
+            info!("User {} enabled two-factor authentication", user.name);
+            Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes }))
+        }
+        Err(err) => {
+            error!("Enabling 2FA of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("2fa/disable")]
+async fn disable_two_factor(
+    auth: AuthenticatedUser,
+    body: web::Json<TwoFactorDisableRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    password::verify(&body.password, &user.password_hash, &data.config.argon2)
+        .map_err(actix_web::error::ErrorUnauthorized)?;
+
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        sqlx::query!(
+            "UPDATE users SET totp_secret = NULL, totp_enabled = FALSE WHERE id = $1;",
+            user.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM two_factor_recovery_codes WHERE user_id = $1;",
+            user.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+    .await;
+    match result {
+        Ok(_) => {
+            info!("User {} disabled two-factor authentication", user.name);
+            Ok(HttpResponse::Ok().finish())
+        }
+        Err(err) => {
+            error!("Disabling 2FA of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[get("me")]
+async fn get_me(auth: AuthenticatedUser) -> Result<impl Responder> {
+    auth.require(Scope::Read)?;
+    Ok(HttpResponse::Ok().json(auth.user.as_reponse()))
+}
+
+#[post("tokens")]
+async fn create_token(
+    auth: AuthenticatedUser,
+    body: web::Json<CreateApiTokenRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    if body.name.trim().is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("Token name is required"));
+    }
+    if body.scopes.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "At least one scope is required",
+        ));
+    }
+    let secret = token::generate();
+    match token::create(user.id, body.name.trim(), &body.scopes, &secret, &data.db).await {
+        Ok(api_token) => {
+            info!("User {} created API token {}", user.name, api_token.id);
+            Ok(HttpResponse::Created().json(api_token.as_response(Some(secret))))
         }
+        Err(err) => {
+            error!("Creating API token for user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[get("tokens")]
+async fn get_tokens(
+    auth: AuthenticatedUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    match token::list(user.id, &data.db).await {
+        Ok(tokens) => {
+            let response: Vec<ApiTokenResponse> = tokens
+                .iter()
+                .map(|api_token| api_token.as_response(None))
+                .collect();
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(err) => {
+            error!("Listing API tokens of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[delete("tokens")]
+async fn revoke_token(
+    auth: AuthenticatedUser,
+    query: web::Query<IdQuery>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    match token::delete(query.id, user.id, &data.db).await {
+        Ok(true) => {
+            info!("API token {} of user {} revoked", query.id, user.name);
+            Ok(HttpResponse::Ok().finish())
+        }
+        Ok(false) => Err(actix_web::error::ErrorNotFound("API token not found")),
+        Err(err) => {
+            error!("Revoking API token {} failed: {err}", query.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("invites")]
+async fn create_invite(
+    auth: AuthenticatedUser,
+    body: web::Json<CreateInviteRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    let is_staff = user.role().is_staff();
+    if !is_staff && !data.config.users_can_invite {
+        return Err(actix_web::error::ErrorForbidden(
+            "Only staff can create invites",
+        ));
+    }
+    if body.max_uses.is_some_and(|uses| uses < 1) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "max_uses has to be at least 1",
+        ));
+    }
+    if body.expires_in_hours.is_some_and(|hours| hours < 1) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "expires_in_hours has to be at least 1",
+        ));
+    }
+    // Invites of regular users are always limited, unlimited ones are up to staff
+    let (max_uses, expires_in_hours) = if is_staff {
+        (body.max_uses, body.expires_in_hours)
     } else {
-        Err(actix_web::error::ErrorNotFound("Invalid session"))
+        (
+            Some(
+                body.max_uses
+                    .unwrap_or(invite::MAX_USER_INVITE_USES)
+                    .min(invite::MAX_USER_INVITE_USES),
+            ),
+            Some(
+                body.expires_in_hours
+                    .unwrap_or(invite::MAX_USER_INVITE_HOURS)
+                    .min(invite::MAX_USER_INVITE_HOURS),
+            ),
+        )
+    };
+    let expires_at = match expires_in_hours {
+        Some(hours) => Some(
+            Duration::try_hours(hours)
+                .and_then(|lifetime| Utc::now().checked_add_signed(lifetime))
+                .ok_or_else(|| {
+                    actix_web::error::ErrorBadRequest("expires_in_hours is too large")
+                })?,
+        ),
+        None => None,
+    };
+
+    let code = invite::generate();
+    let active_limit = (!is_staff).then_some(invite::MAX_USER_ACTIVE_INVITES);
+    match invite::create(user.id, &code, max_uses, expires_at, active_limit, &data.db).await {
+        Ok(Some(created)) => {
+            info!("User {} created invite {}", user.name, created.id);
+            Ok(HttpResponse::Created().json(created.as_response(Some(code), Vec::new())))
+        }
+        Ok(None) => Err(actix_web::error::ErrorConflict(format!(
+            "At most {} invites can be active at once, delete one or wait for it to expire",
+            invite::MAX_USER_ACTIVE_INVITES
+        ))),
+        Err(err) => {
+            error!("Creating invite for user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[get("invites")]
+async fn get_invites(
+    auth: AuthenticatedUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    match invite::list(user.id, &data.db).await {
+        Ok(invites) => {
+            let response: Vec<InviteResponse> = invites
+                .into_iter()
+                .map(|(invite, invited)| invite.as_response(None, invited))
+                .collect();
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(err) => {
+            error!("Listing invites of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[delete("invites")]
+async fn revoke_invite(
+    auth: AuthenticatedUser,
+    query: web::Query<IdQuery>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    // Staff may revoke any invite, users only their own
+    let created_by = (!user.role().is_staff()).then_some(user.id);
+    match invite::delete(query.id, created_by, &data.db).await {
+        Ok(true) => {
+            info!("Invite {} revoked by {}", query.id, user.name);
+            Ok(HttpResponse::Ok().finish())
+        }
+        Ok(false) => Err(actix_web::error::ErrorNotFound("Invite not found")),
+        Err(err) => {
+            error!("Revoking invite {} failed: {err}", query.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
     }
 }
 
-async fn auth_user(username: String, password: String, db: Pool<Postgres>) -> Result<User, Error> {
-    match sqlx::query_as!(User, "SELECT * FROM users WHERE(name LIKE $1);", username)
-        .fetch_one(&db)
+#[post("recover")]
+async fn recover_account(
+    req: HttpRequest,
+    body: web::Json<RecoverAccountRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    // Guessing recovery codes counts against the same limits as guessing passwords
+    let user_subject = lockout::user_subject(&username::key(&body.name));
+    let subjects = [
+        user_subject.clone(),
+        lockout::client_subject(&client::address(&req, &data.config)),
+    ];
+    check_lockout(&subjects, &body.name, &data).await?;
+
+    let user = match sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE name_key = $1;",
+        username::key(&body.name)
+    )
+    .fetch_optional(&data.db)
+    .await
+    {
+        Ok(user) => user,
+        Err(err) => {
+            error!("Looking up user {} failed: {err}", body.name);
+            return Err(actix_web::error::ErrorInternalServerError(err));
+        }
+    };
+    let password_hash = password::hash(&body.new_password, &data.config.argon2).map_err(|err| {
+        error!("Hashing password of user {} failed", body.name);
+        actix_web::error::ErrorInternalServerError(err)
+    })?;
+
+    let result = match &user {
+        Some(user) => {
+            async {
+                let mut tx = data.db.begin().await?;
+                if !recovery::use_code(user.id, &body.recovery_code, &mut tx).await? {
+                    return Ok(None);
+                }
+                sqlx::query!(
+                    "UPDATE users SET password_hash = $1 WHERE id = $2;",
+                    password_hash,
+                    user.id
+                )
+                .execute(&mut *tx)
+                .await?;
+                let remaining = recovery::remaining(user.id, &mut tx).await?;
+                data.sessions.delete_all_in(user.id, &mut tx).await?;
+                tx.commit().await?;
+                Ok::<_, sqlx::Error>(Some(remaining))
+            }
+            .await
+        }
+        None => Ok(None),
+    };
+
+    let recorded = match &result {
+        Ok(Some(_)) => lockout::reset(&user_subject, &data.db).await,
+        Ok(None) => lockout::record_failure(&subjects, &data.config.login_lockout, &data.db).await,
+        Err(_) => Ok(()),
+    };
+    if let Err(err) = recorded {
+        error!("Updating login failures of {} failed: {err}", body.name);
+    }
+
+    match result {
+        Ok(Some(remaining)) => {
+            info!(
+                "User {} recovered their account, {remaining} recovery codes left",
+                body.name
+            );
+            Ok(HttpResponse::Ok()
+                .cookie(session::removal_cookie())
+                .json(RecoverAccountResponse {
+                    remaining_recovery_codes: remaining,
+                }))
+        }
+        Ok(None) => Err(Error::AuthFailed.into()),
+        Err(err) => {
+            error!("Recovering account of {} failed: {err}", body.name);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("recovery-codes")]
+async fn regenerate_recovery_codes(
+    auth: AuthenticatedUser,
+    body: web::Json<NewRecoveryCodesRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    password::verify(&body.password, &user.password_hash, &data.config.argon2)
+        .map_err(actix_web::error::ErrorUnauthorized)?;
+
+    let recovery_codes = two_factor::generate_recovery_codes();
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        recovery::store_codes(user.id, &recovery_codes, &mut tx).await?;
+        tx.commit().await
+    }
+    .await;
+
+    match result {
+        Ok(_) => {
+            info!("User {} generated new recovery codes", user.name);
+            Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes }))
+        }
+        Err(err) => {
+            error!("Storing recovery codes of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[post("role")]
+async fn set_role(
+    auth: AuthenticatedUser,
+    body: web::Json<SetRoleRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    auth.require_role(Role::Admin)?;
+    // Keeps the last admin from locking everybody out of role management
+    if body.user_id == auth.user.id {
+        return Err(actix_web::error::ErrorForbidden(
+            "Cannot change your own role",
+        ));
+    }
+    match role::set(body.user_id, body.role, &data.db).await {
+        Ok(Some(user)) => {
+            info!(
+                "User {} changed role of {} to {}",
+                auth.user.name, user.name, body.role
+            );
+            Ok(HttpResponse::Ok().json(user.as_reponse()))
+        }
+        Ok(None) => Err(Error::UserNotFound.into()),
+        Err(err) => {
+            error!("Changing role of user {} failed: {err}", body.user_id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[get("oidc/login")]
+async fn oidc_login(
+    user: MaybeUser,
+    query: web::Query<OidcLoginQuery>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    let oidc = oidc_client(&data)?;
+    let link_user_id = match (&user.0, query.link) {
+        (_, false) => None,
+        (Some(auth), true) => {
+            auth.session_id()?;
+            Some(auth.user.id)
+        }
+        (None, true) => {
+            return Err(actix_web::error::ErrorUnauthorized(
+                "Log in to link an identity",
+            ))
+        }
+    };
+
+    let request = oidc.authorize().await.map_err(|err| {
+        error!("Starting OIDC login failed: {err}");
+        actix_web::error::ErrorBadGateway(err)
+    })?;
+    if let Err(err) = identity::create_login(&request, link_user_id, &data.db).await {
+        error!("Storing OIDC login failed: {err}");
+        return Err(actix_web::error::ErrorInternalServerError(err));
+    }
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, request.url))
+        .cookie(oidc::state_cookie(&request.state))
+        .finish())
+}
+
+/// The provider sends the browser here, which is then sent on to the frontend.
+/// Failures are reported to the frontend with an `oidc_error` parameter.
+/// The `oidc_state` cookie is dropped whatever the outcome.
+#[get("oidc/callback")]
+async fn oidc_callback(
+    req: HttpRequest,
+    query: web::Query<OidcCallbackQuery>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    let oidc = oidc_client(&data)?;
+    let redirect = |params: &[(&str, &str)]| {
+        HttpResponse::Found()
+            .insert_header((header::LOCATION, oidc.config.frontend_redirect(params)))
+            .cookie(oidc::state_removal_cookie())
+            .finish()
+    };
+
+    if !oidc::state_matches(&req, &query.state) {
+        info!("OIDC callback did not come from the browser which started the login");
+        return Ok(redirect(&[("oidc_error", "invalid_state")]));
+    }
+    let login = match identity::take_login(&query.state, &data.db).await {
+        Ok(Some(login)) => login,
+        Ok(None) => return Ok(redirect(&[("oidc_error", "login_expired")])),
+        Err(err) => {
+            error!("Looking up OIDC login failed: {err}");
+            return Err(actix_web::error::ErrorInternalServerError(err));
+        }
+    };
+    let Some(code) = &query.code else {
+        let reason = query.error.as_deref().unwrap_or("invalid_request");
+        info!("OIDC login was not completed at the provider: {reason}");
+        return Ok(redirect(&[("oidc_error", reason)]));
+    };
+    let claims = match oidc
+        .exchange(code, &login.code_verifier, &login.nonce)
         .await
     {
-        Ok(user) => {
-            debug!("User {} found.", username);
-            let salt = SaltString::from_b64(&user.salt).expect("Failed decoding salt");
-            let argon2 = Argon2::default();
-            let password_hash = argon2
-                .hash_password(password.as_bytes(), &salt)
-                .expect("Failed hashing")
-                .to_string();
-            if user.password_hash == password_hash {
-                Ok(user)
-            } else {
-                Err(Error::AuthFailed)
+        Ok(claims) => claims,
+        Err(err) => {
+            error!("Completing OIDC login failed: {err}");
+            return Ok(redirect(&[("oidc_error", "provider_error")]));
+        }
+    };
+
+    if let Some(user_id) = login.link_user_id {
+        let linked = match data.db.acquire().await {
+            Ok(mut conn) => identity::link(user_id, &claims.iss, &claims.sub, &mut conn).await,
+            Err(err) => Err(err),
+        };
+        return match linked {
+            Ok(_) => {
+                info!(
+                    "User {user_id} linked identity {} of {}",
+                    claims.sub, claims.iss
+                );
+                Ok(redirect(&[("oidc_linked", "true")]))
+            }
+            Err(err)
+                if err
+                    .as_database_error()
+                    .is_some_and(|err| err.is_unique_violation()) =>
+            {
+                Ok(redirect(&[("oidc_error", "already_linked")]))
+            }
+            Err(err) => {
+                error!("Linking identity to user {user_id} failed: {err}");
+                Err(actix_web::error::ErrorInternalServerError(err))
+            }
+        };
+    }
+
+    match identity::find_user(&claims.iss, &claims.sub, &data.db).await {
+        Ok(Some(user)) if user.totp_enabled => {
+            // The provider replaces the password, not the second factor
+            let token = two_factor::create_pending_login(user.id, &data.db)
+                .await
+                .map_err(|err| {
+                    error!("Creating pending login for user {} failed: {err}", user.id);
+                    actix_web::error::ErrorInternalServerError(err)
+                })?;
+            let mut response = redirect(&[("two_factor", "required")]);
+            response
+                .add_cookie(&two_factor::pending_login_cookie(token))
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+            Ok(response)
+        }
+        Ok(Some(user)) => {
+            let (_, token) = session::create(user.id, &req, data.sessions.as_ref())
+                .await
+                .map_err(|err| {
+                    error!("Creating session for user {} failed: {err}", user.id);
+                    actix_web::error::ErrorInternalServerError(err)
+                })?;
+            info!("User {} logged in with OIDC", user.name);
+            let mut response = redirect(&[]);
+            response
+                .add_cookie(&session::cookie(token))
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+            Ok(response)
+        }
+        Ok(None) if !oidc.config.auto_provision => Ok(redirect(&[("oidc_error", "no_account")])),
+        Ok(None) => {
+            // Only a suggestion, the user confirms or changes it in `oidc_signup`
+            let suggested_name = claims
+                .preferred_username
+                .as_deref()
+                .or(claims.name.as_deref())
+                .and_then(|name| username::normalize(name).ok());
+            match identity::create_signup(
+                &claims.iss,
+                &claims.sub,
+                suggested_name.as_deref(),
+                &data.db,
+            )
+            .await
+            {
+                Ok(signup_id) => {
+                    let mut response = redirect(&[
+                        ("oidc_signup", "required"),
+                        ("name", suggested_name.as_deref().unwrap_or_default()),
+                    ]);
+                    response
+                        .add_cookie(&oidc::signup_cookie(signup_id))
+                        .map_err(actix_web::error::ErrorInternalServerError)?;
+                    Ok(response)
+                }
+                Err(err) => {
+                    error!("Storing OIDC signup failed: {err}");
+                    Err(actix_web::error::ErrorInternalServerError(err))
+                }
+            }
+        }
+        Err(err) => {
+            error!(
+                "Looking up identity {} of {} failed: {err}",
+                claims.sub, claims.iss
+            );
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+/// Creates the account of a new identity once it picked a username. This does
+/// not depend on `REGISTRATION_MODE`, `OIDC_AUTO_PROVISION` controls it instead.
+#[post("oidc/signup")]
+async fn oidc_signup(
+    req: HttpRequest,
+    body: web::Json<OidcSignupRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    let oidc = oidc_client(&data)?;
+    if !oidc.config.auto_provision {
+        return Err(Error::RegistrationClosed.into());
+    }
+    let Some(signup_id) = oidc::signup_from_request(&req) else {
+        return Err(actix_web::error::ErrorUnauthorized(
+            "Sign-up expired, please log in again",
+        ));
+    };
+    let name = username::normalize(&body.name)?;
+    let name_key = username::key(&name);
+    check_name_available(&name, &name_key, &data).await?;
+
+    // Nobody knows this password, a local one can be set with a recovery code
+    let password_hash =
+        password::hash(&oidc::random_token(), &data.config.argon2).map_err(|err| {
+            error!("Hashing password of user {} failed", name);
+            actix_web::error::ErrorInternalServerError(err)
+        })?;
+    let recovery_codes = two_factor::generate_recovery_codes();
+    let result = async {
+        let mut tx = data.db.begin().await?;
+        let Some(signup) = identity::take_signup(signup_id, &mut tx).await? else {
+            return Ok(None);
+        };
+        let user = sqlx::query_as!(
+            User,
+            "INSERT INTO users (id, name, password_hash, created_at, last_active, name_key)
+            VALUES($1,$2,$3,$4,$5,$6) RETURNING *;",
+            Uuid::new_v4(),
+            name,
+            password_hash,
+            Utc::now(),
+            Utc::now(),
+            name_key,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        identity::link(user.id, &signup.issuer, &signup.subject, &mut tx).await?;
+        recovery::store_codes(user.id, &recovery_codes, &mut tx).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some(user))
+    }
+    .await;
+
+    match result {
+        Ok(None) => Err(actix_web::error::ErrorUnauthorized(
+            "Sign-up expired, please log in again",
+        )),
+        Ok(Some(user)) => {
+            info!("User {} registered with OIDC", name);
+            let user = promote_bootstrap_admin(user, &data).await;
+            let (_, token) = session::create(user.id, &req, data.sessions.as_ref())
+                .await
+                .map_err(|err| {
+                    error!("Creating session for user {} failed: {err}", user.id);
+                    actix_web::error::ErrorInternalServerError(err)
+                })?;
+            Ok(HttpResponse::Created()
+                .cookie(session::cookie(token))
+                .cookie(oidc::signup_removal_cookie())
+                .json(RegisterResponse {
+                    user: user.as_reponse(),
+                    recovery_codes,
+                }))
+        }
+        Err(err)
+            if err
+                .as_database_error()
+                .is_some_and(|err| err.is_unique_violation()) =>
+        {
+            Err(Error::UsernameTaken.into())
+        }
+        Err(err) => {
+            error!("{err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[get("identities")]
+async fn get_identities(
+    auth: AuthenticatedUser,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    match identity::list(user.id, &data.db).await {
+        Ok(identities) => {
+            let response: Vec<IdentityResponse> =
+                identities.iter().map(Identity::as_response).collect();
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(err) => {
+            error!("Listing identities of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+#[delete("identities")]
+async fn unlink_identity(
+    auth: AuthenticatedUser,
+    body: web::Json<UnlinkIdentityRequest>,
+    data: web::Data<SharedState>,
+) -> Result<impl Responder> {
+    auth.session_id()?;
+    let user = auth.user;
+    // Accounts created with OIDC only get a password through account recovery
+    password::verify(&body.password, &user.password_hash, &data.config.argon2)
+        .map_err(actix_web::error::ErrorUnauthorized)?;
+    match identity::unlink(body.id, user.id, &data.db).await {
+        Ok(true) => {
+            info!("User {} unlinked identity {}", user.name, body.id);
+            Ok(HttpResponse::Ok().finish())
+        }
+        Ok(false) => Err(actix_web::error::ErrorNotFound("Identity not found")),
+        Err(err) => {
+            error!("Unlinking identity {} failed: {err}", body.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+fn oidc_client(data: &SharedState) -> Result<&OidcClient> {
+    data.oidc
+        .as_deref()
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Single sign-on is not configured"))
+}
+
+/// Accepts either a current TOTP code or an unused recovery code
+async fn check_second_factor(user: &User, code: &str, data: &SharedState) -> Result<bool> {
+    let Some(secret) = &user.totp_secret else {
+        return Ok(false);
+    };
+    if let Ok(step) = two_factor::verify_code(secret, &user.name, code) {
+        return use_time_step(user, step, data).await;
+    }
+    match two_factor::use_recovery_code(user.id, code, &data.db).await {
+        Ok(used) => {
+            if used {
+                info!("User {} logged in with a recovery code", user.name);
             }
+            Ok(used)
+        }
+        Err(err) => {
+            error!("Checking recovery code of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+/// Marks the time step of a valid code as used, a code seen before is rejected
+async fn use_time_step(user: &User, step: i64, data: &SharedState) -> Result<bool> {
+    match two_factor::use_time_step(user.id, step, &data.db).await {
+        Ok(true) => Ok(true),
+        Ok(false) => {
+            info!("Reused 2FA code of user {} rejected", user.name);
+            Ok(false)
+        }
+        Err(err) => {
+            error!("Recording 2FA code of user {} failed: {err}", user.id);
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+/// Rejects login attempts for a name or from a client which is locked out
+async fn check_lockout(subjects: &[String], name: &str, data: &SharedState) -> Result<()> {
+    match lockout::retry_after(subjects, &data.db).await {
+        Ok(None) => Ok(()),
+        Ok(Some(retry_after)) => {
+            info!("Login of {name} rejected, locked out for {retry_after}s");
+            Err(Error::TooManyAttempts { retry_after }.into())
         }
-        Err(_) => {
+        Err(err) => {
+            error!("Checking login failures of {name} failed: {err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+/// Whether the name is the configured `BOOTSTRAP_ADMIN`
+fn is_bootstrap_admin(name_key: &str, data: &SharedState) -> bool {
+    data.config
+        .bootstrap_admin
+        .as_deref()
+        .is_some_and(|admin| username::key(admin) == name_key)
+}
+
+/// Makes a freshly registered `BOOTSTRAP_ADMIN` admin while there is none yet
+async fn promote_bootstrap_admin(user: User, data: &SharedState) -> User {
+    let name_key = username::key(&user.name);
+    if !is_bootstrap_admin(&name_key, data) {
+        return user;
+    }
+    match role::bootstrap_admin(&name_key, &data.db).await {
+        Ok(Some(admin)) => {
+            info!("User {} bootstrapped as admin", admin.name);
+            admin
+        }
+        Ok(None) => user,
+        Err(err) => {
+            error!("Bootstrapping admin {} failed: {err}", user.name);
+            user
+        }
+    }
+}
+
+/// Rejects names which are taken or look like a taken name
+async fn check_name_available(name: &str, name_key: &str, data: &SharedState) -> Result<()> {
+    match sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM users WHERE name_key = $1);",
+        name_key
+    )
+    .fetch_one(&data.db)
+    .await
+    {
+        Ok(Some(false)) => Ok(()),
+        Ok(_) => Err(Error::UsernameTaken.into()),
+        Err(err) => {
+            error!("Checking availability of username {name} failed: {err}");
+            Err(actix_web::error::ErrorInternalServerError(err))
+        }
+    }
+}
+
+/// Creates a session for an authenticated user and sets its cookie
+async fn start_session(user: &User, req: &HttpRequest, data: &SharedState) -> Result<HttpResponse> {
+    let (_, token) = session::create(user.id, req, data.sessions.as_ref())
+        .await
+        .map_err(|err| {
+            error!("Creating session for user {} failed: {err}", user.id);
+            actix_web::error::ErrorInternalServerError(err)
+        })?;
+
+    Ok(HttpResponse::Ok()
+        .cookie(session::cookie(token))
+        .json(user.as_reponse()))
+}
+
+async fn auth_user(username: &str, password: &str, data: &SharedState) -> Result<User, Error> {
+    // Names are unique by key, so logging in ignores case and look-alike characters
+    let user = match sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE name_key = $1;",
+        username::key(username)
+    )
+    .fetch_optional(&data.db)
+    .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => {
             error!("User {} does not exist", username);
-            Err(Error::UserNotFound)
+            return Err(Error::UserNotFound);
+        }
+        Err(err) => {
+            error!("Looking up user {} failed: {err}", username);
+            return Err(Error::UserNotFound);
+        }
+    };
+    debug!("User {} found.", username);
+
+    if password::verify(password, &user.password_hash, &data.config.argon2)?
+        == Verified::NeedsRehash
+    {
+        rehash_password(&user, password, data).await;
+    }
+    Ok(user)
+}
+
+/// Replaces a hash made with outdated Argon2 parameters, failures only get logged
+/// since the user already authenticated successfully
+async fn rehash_password(user: &User, password: &str, data: &SharedState) {
+    let password_hash = match password::hash(password, &data.config.argon2) {
+        Ok(password_hash) => password_hash,
+        Err(err) => {
+            error!("Rehashing password of user {} failed: {err}", user.id);
+            return;
         }
+    };
+    match sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE id = $2;",
+        password_hash,
+        user.id
+    )
+    .execute(&data.db)
+    .await
+    {
+        Ok(_) => info!("Password hash of user {} upgraded", user.name),
+        Err(err) => error!(
+            "Storing rehashed password of user {} failed: {err}",
+            user.id
+        ),
     }
 }
 
 pub fn user_service(conf: &mut web::ServiceConfig) {
     let scope = web::scope("api/users")
+        .wrap(from_fn(csrf::protect))
         .service(register_user)
         .service(login_user)
+        .service(login_two_factor)
         .service(get_users)
-        .service(auth_session);
+        .service(auth_session)
+        .service(logout_user)
+        .service(get_sessions)
+        .service(revoke_all_sessions)
+        .service(revoke_session)
+        .service(change_password)
+        .service(delete_user)
+        .service(enroll_two_factor)
+        .service(confirm_two_factor)
+        .service(disable_two_factor)
+        .service(get_me)
+        .service(create_token)
+        .service(get_tokens)
+        .service(revoke_token)
+        .service(create_invite)
+        .service(get_invites)
+        .service(revoke_invite)
+        .service(recover_account)
+        .service(regenerate_recovery_codes)
+        .service(set_role)
+        .service(oidc_login)
+        .service(oidc_callback)
+        .service(oidc_signup)
+        .service(get_identities)
+        .service(unlink_identity);
 
     conf.service(scope);
 }
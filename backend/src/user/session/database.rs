@@ -0,0 +1,103 @@
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use super::{SessionStore, SESSION_LIFETIME_DAYS};
+use crate::user::model::Session;
+
+/// Sessions in the `sessions` table, the cookie holds the session id
+pub struct DatabaseSessionStore {
+    db: Pool<Postgres>,
+}
+
+impl DatabaseSessionStore {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        DatabaseSessionStore { db }
+    }
+}
+
+#[async_trait]
+impl SessionStore for DatabaseSessionStore {
+    async fn create(
+        &self,
+        user_id: Uuid,
+        user_agent: Option<&str>,
+    ) -> Result<(Session, String), sqlx::Error> {
+        let now = Utc::now();
+        let session = sqlx::query_as!(
+            Session,
+            "INSERT INTO sessions VALUES($1,$2,$3,$4,$5) RETURNING *;",
+            Uuid::new_v4(),
+            user_id,
+            now,
+            now + Duration::days(SESSION_LIFETIME_DAYS),
+            user_agent,
+        )
+        .fetch_one(&self.db)
+        .await?;
+        let token = session.id.to_string();
+        Ok((session, token))
+    }
+
+    async fn find(&self, token: &str) -> Result<Option<Session>, sqlx::Error> {
+        let Ok(session_id) = Uuid::parse_str(token) else {
+            return Ok(None);
+        };
+        sqlx::query_as!(
+            Session,
+            "SELECT * FROM sessions WHERE id = $1 AND expires_at > $2;",
+            session_id,
+            Utc::now(),
+        )
+        .fetch_optional(&self.db)
+        .await
+    }
+
+    async fn list_active(&self, user_id: Uuid) -> Result<Vec<Session>, sqlx::Error> {
+        sqlx::query_as!(
+            Session,
+            "SELECT * FROM sessions WHERE user_id = $1 AND expires_at > $2 ORDER BY created_at DESC;",
+            user_id,
+            Utc::now(),
+        )
+        .fetch_all(&self.db)
+        .await
+    }
+
+    async fn delete(&self, session_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM sessions WHERE id = $1 AND user_id = $2;",
+            session_id,
+            user_id
+        )
+        .execute(&self.db)
+        .await
+        .map(|result| result.rows_affected() > 0)
+    }
+
+    async fn delete_all(&self, user_id: Uuid) -> Result<u64, sqlx::Error> {
+        sqlx::query!("DELETE FROM sessions WHERE user_id = $1;", user_id)
+            .execute(&self.db)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    async fn delete_all_in(
+        &self,
+        user_id: Uuid,
+        conn: &mut PgConnection,
+    ) -> Result<u64, sqlx::Error> {
+        sqlx::query!("DELETE FROM sessions WHERE user_id = $1;", user_id)
+            .execute(conn)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    async fn delete_expired(&self) -> Result<u64, sqlx::Error> {
+        sqlx::query!("DELETE FROM sessions WHERE expires_at <= $1;", Utc::now())
+            .execute(&self.db)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
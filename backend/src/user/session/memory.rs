@@ -0,0 +1,134 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use super::{SessionStore, SESSION_LIFETIME_DAYS};
+use crate::user::model::Session;
+
+/// Sessions kept in process memory, the cookie holds the session id.
+/// Meant for tests and single instance setups which can live with logging
+/// everybody out on restart.
+#[derive(Default)]
+pub struct MemorySessionStore {
+    sessions: Mutex<HashMap<Uuid, Session>>,
+}
+
+impl MemorySessionStore {
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, Session>> {
+        // The map stays consistent even if a holder of the lock panicked
+        self.sessions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn create(
+        &self,
+        user_id: Uuid,
+        user_agent: Option<&str>,
+    ) -> Result<(Session, String), sqlx::Error> {
+        let now = Utc::now();
+        let session = Session {
+            id: Uuid::new_v4(),
+            user_id,
+            created_at: now,
+            expires_at: now + Duration::days(SESSION_LIFETIME_DAYS),
+            user_agent: user_agent.map(str::to_string),
+        };
+        self.sessions().insert(session.id, session.clone());
+        let token = session.id.to_string();
+        Ok((session, token))
+    }
+
+    async fn find(&self, token: &str) -> Result<Option<Session>, sqlx::Error> {
+        let Ok(session_id) = Uuid::parse_str(token) else {
+            return Ok(None);
+        };
+        Ok(self
+            .sessions()
+            .get(&session_id)
+            .filter(|session| session.expires_at > Utc::now())
+            .cloned())
+    }
+
+    async fn list_active(&self, user_id: Uuid) -> Result<Vec<Session>, sqlx::Error> {
+        let now = Utc::now();
+        let mut sessions: Vec<Session> = self
+            .sessions()
+            .values()
+            .filter(|session| session.user_id == user_id && session.expires_at > now)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.created_at));
+        Ok(sessions)
+    }
+
+    async fn delete(&self, session_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let mut sessions = self.sessions();
+        match sessions.get(&session_id) {
+            Some(session) if session.user_id == user_id => {
+                sessions.remove(&session_id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete_all(&self, user_id: Uuid) -> Result<u64, sqlx::Error> {
+        let mut sessions = self.sessions();
+        let before = sessions.len();
+        sessions.retain(|_, session| session.user_id != user_id);
+        Ok((before - sessions.len()) as u64)
+    }
+
+    async fn delete_expired(&self) -> Result<u64, sqlx::Error> {
+        let now = Utc::now();
+        let mut sessions = self.sessions();
+        let before = sessions.len();
+        sessions.retain(|_, session| session.expires_at > now);
+        Ok((before - sessions.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_session_round_trip() {
+        let store = MemorySessionStore::default();
+        let user_id = Uuid::new_v4();
+        let (session, token) = store.create(user_id, Some("curl")).await.unwrap();
+        let found = store.find(&token).await.unwrap().unwrap();
+        assert_eq!(found.id, session.id);
+        assert_eq!(found.user_agent.as_deref(), Some("curl"));
+        assert!(store.find("not a session").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_delete_only_own_sessions() {
+        let store = MemorySessionStore::default();
+        let user_id = Uuid::new_v4();
+        let (session, token) = store.create(user_id, None).await.unwrap();
+        store.create(user_id, None).await.unwrap();
+        assert!(!store.delete(session.id, Uuid::new_v4()).await.unwrap());
+        assert!(store.delete(session.id, user_id).await.unwrap());
+        assert!(store.find(&token).await.unwrap().is_none());
+        assert_eq!(store.list_active(user_id).await.unwrap().len(), 1);
+        assert_eq!(store.delete_all(user_id).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_sessions_are_ignored() {
+        let store = MemorySessionStore::default();
+        let user_id = Uuid::new_v4();
+        let (session, token) = store.create(user_id, None).await.unwrap();
+        store.sessions().get_mut(&session.id).unwrap().expires_at = Utc::now();
+        assert!(store.find(&token).await.unwrap().is_none());
+        assert_eq!(store.delete_expired().await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{fmt, sync::Arc};
+
+use actix_web::{
+    cookie::{time, Cookie},
+    http::header,
+    HttpRequest,
+};
+use async_trait::async_trait;
+use log::{error, info};
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::common::rate_limit;
+
+use super::model::{Session, User};
+use super::{identity, lockout, two_factor};
+
+pub(crate) mod database;
+pub(crate) mod memory;
+pub(crate) mod signed;
+
+/// Name of the cookie holding the session
+pub const SESSION_COOKIE: &str = "session_id";
+/// How long a session stays valid, matches `max_age` of the `session_id` cookie
+pub const SESSION_LIFETIME_DAYS: i64 = 3;
+/// How often expired sessions are removed from the store
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Storage of login sessions. Each session is identified by the value of its
+/// `session_id` cookie, which only the store knows how to interpret.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Starts a new session for the user, returns it together with the cookie value
+    async fn create(
+        &self,
+        user_id: Uuid,
+        user_agent: Option<&str>,
+    ) -> Result<(Session, String), sqlx::Error>;
+
+    /// Resolves a cookie value to its session, if the session is valid and has not expired
+    async fn find(&self, token: &str) -> Result<Option<Session>, sqlx::Error>;
+
+    /// Returns all sessions of the user which have not expired yet, newest first
+    async fn list_active(&self, user_id: Uuid) -> Result<Vec<Session>, sqlx::Error>;
+
+    /// Ends a single session of the user, returns whether it existed
+    async fn delete(&self, session_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error>;
+
+    /// Ends every session of the user
+    async fn delete_all(&self, user_id: Uuid) -> Result<u64, sqlx::Error>;
+
+    /// Ends every session of the user as part of the transaction of `conn`, so
+    /// they only end if it commits. Stores outside the database end them right
+    /// away, callers do this before committing so a failure still rolls back.
+    async fn delete_all_in(
+        &self,
+        user_id: Uuid,
+        _conn: &mut PgConnection,
+    ) -> Result<u64, sqlx::Error> {
+        self.delete_all(user_id).await
+    }
+
+    /// Forgets sessions which have expired, returns how many were removed
+    async fn delete_expired(&self) -> Result<u64, sqlx::Error>;
+}
+
+/// Key for signing stateless sessions, kept out of debug output
+#[derive(Clone)]
+pub struct SessionKey(pub Vec<u8>);
+
+impl fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SessionKey(..)")
+    }
+}
+
+/// Where sessions are kept, selected with `SESSION_STORE`
+#[derive(Clone, Debug)]
+pub enum SessionBackend {
+    /// Process memory, sessions are lost on restart and not shared between instances
+    Memory,
+    /// The `sessions` table, shared by all instances
+    Database,
+    /// Signed cookies without any server side session state. The first key
+    /// signs new sessions, the others are still accepted to allow key rotation.
+    Signed { keys: Vec<SessionKey> },
+}
+
+impl SessionBackend {
+    pub fn build(&self, db: &Pool<Postgres>) -> Arc<dyn SessionStore> {
+        match self {
+            SessionBackend::Memory => Arc::new(memory::MemorySessionStore::default()),
+            SessionBackend::Database => Arc::new(database::DatabaseSessionStore::new(db.clone())),
+            SessionBackend::Signed { keys } => {
+                Arc::new(signed::SignedSessionStore::new(keys.clone(), db.clone()))
+            }
+        }
+    }
+}
+
+/// Creates a new session for the user, remembering the client it was created from
+pub async fn create(
+    user_id: Uuid,
+    req: &HttpRequest,
+    store: &dyn SessionStore,
+) -> Result<(Session, String), sqlx::Error> {
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok());
+    store.create(user_id, user_agent).await
+}
+
+/// Returns a valid session together with its owner
+pub async fn find_user(
+    token: &str,
+    store: &dyn SessionStore,
+    db: &Pool<Postgres>,
+) -> Result<Option<(Session, User)>, sqlx::Error> {
+    let Some(session) = store.find(token).await? else {
+        return Ok(None);
+    };
+    let user = sqlx::query_as!(User, "SELECT * FROM users WHERE id = $1;", session.user_id)
+        .fetch_optional(db)
+        .await?;
+    Ok(user.map(|user| (session, user)))
+}
+
+/// Reads the session cookie from the request, `None` if it is missing
+pub fn token_from_request(req: &HttpRequest) -> Option<String> {
+    req.cookie(SESSION_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// Periodically removes expired sessions, pending 2FA and OIDC logins,
+/// forgotten login failures and past rate limit windows, meant to be spawned once at startup
+pub async fn sweep_expired(db: Pool<Postgres>, store: Arc<dyn SessionStore>) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        match store.delete_expired().await {
+            Ok(0) => {}
+            Ok(count) => info!("Removed {count} expired sessions"),
+            Err(err) => error!("Removing expired sessions failed: {err}"),
+        }
+        if let Err(err) = two_factor::delete_expired_pending_logins(&db).await {
+            error!("Removing expired pending logins failed: {err}");
+        }
+        if let Err(err) = identity::delete_expired(&db).await {
+            error!("Removing expired OIDC logins failed: {err}");
+        }
+        if let Err(err) = lockout::delete_stale(&db).await {
+            error!("Removing stale login failures failed: {err}");
+        }
+        if let Err(err) = rate_limit::delete_stale(&db).await {
+            error!("Removing stale rate limit windows failed: {err}");
+        }
+    }
+}
+
+pub fn cookie(token: String) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE, token)
+        .max_age(time::Duration::days(SESSION_LIFETIME_DAYS))
+        .path("/")
+        .finish()
+}
+
+/// Cookie which makes the browser drop its `session_id`
+pub fn removal_cookie() -> Cookie<'static> {
+    let mut cookie = Cookie::build(SESSION_COOKIE, "").path("/").finish();
+    cookie.make_removal();
+    cookie
+}
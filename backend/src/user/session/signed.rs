@@ -0,0 +1,264 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use super::{SessionKey, SessionStore, SESSION_LIFETIME_DAYS};
+use crate::user::model::Session;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Sessions living only in an HMAC signed cookie. Nothing is stored per
+/// session, so sessions cannot be listed. Revoking a single session keeps its
+/// id in `revoked_sessions` until it would have expired, revoking all sessions
+/// of a user bumps `users.session_generation`, which every cookie has to match.
+pub struct SignedSessionStore {
+    keys: Vec<SessionKey>,
+    db: Pool<Postgres>,
+}
+
+/// Content of a session cookie, signed as `id.user_id.generation.created.expires`
+#[derive(Debug, PartialEq)]
+struct Claims {
+    session_id: Uuid,
+    user_id: Uuid,
+    generation: i32,
+    created_at: i64,
+    expires_at: i64,
+}
+
+impl Claims {
+    fn payload(&self) -> String {
+        format!(
+            "{}.{}.{}.{}.{}",
+            self.session_id, self.user_id, self.generation, self.created_at, self.expires_at
+        )
+    }
+
+    fn as_session(&self) -> Session {
+        Session {
+            id: self.session_id,
+            user_id: self.user_id,
+            created_at: DateTime::from_timestamp(self.created_at, 0).unwrap_or_default(),
+            expires_at: DateTime::from_timestamp(self.expires_at, 0).unwrap_or_default(),
+            user_agent: None,
+        }
+    }
+}
+
+fn mac(key: &SessionKey, payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(&key.0).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Signs the claims with `key`, the result is the cookie value
+fn encode(claims: &Claims, key: &SessionKey) -> String {
+    let payload = claims.payload();
+    let signature: String = mac(key, &payload)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("{payload}.{signature}")
+}
+
+/// Checks the signature against all keys and returns the claims of an unexpired cookie
+fn decode(token: &str, keys: &[SessionKey], now: i64) -> Option<Claims> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    let signature = decode_hex(signature)?;
+    if !keys
+        .iter()
+        .any(|key| mac(key, payload).verify_slice(&signature).is_ok())
+    {
+        return None;
+    }
+
+    let mut parts = payload.split('.');
+    let claims = Claims {
+        session_id: parts.next()?.parse().ok()?,
+        user_id: parts.next()?.parse().ok()?,
+        generation: parts.next()?.parse().ok()?,
+        created_at: parts.next()?.parse().ok()?,
+        expires_at: parts.next()?.parse().ok()?,
+    };
+    if parts.next().is_some() || claims.expires_at <= now {
+        return None;
+    }
+    Some(claims)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+impl SignedSessionStore {
+    pub fn new(keys: Vec<SessionKey>, db: Pool<Postgres>) -> Self {
+        assert!(!keys.is_empty(), "signed sessions need at least one key");
+        SignedSessionStore { keys, db }
+    }
+
+    async fn generation(&self, user_id: Uuid) -> Result<Option<i32>, sqlx::Error> {
+        sqlx::query_scalar!(
+            "SELECT session_generation FROM users WHERE id = $1;",
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await
+    }
+}
+
+#[async_trait]
+impl SessionStore for SignedSessionStore {
+    async fn create(
+        &self,
+        user_id: Uuid,
+        _user_agent: Option<&str>,
+    ) -> Result<(Session, String), sqlx::Error> {
+        let generation = self
+            .generation(user_id)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            session_id: Uuid::new_v4(),
+            user_id,
+            generation,
+            created_at: now,
+            expires_at: now + Duration::days(SESSION_LIFETIME_DAYS).num_seconds(),
+        };
+        Ok((claims.as_session(), encode(&claims, &self.keys[0])))
+    }
+
+    async fn find(&self, token: &str) -> Result<Option<Session>, sqlx::Error> {
+        let Some(claims) = decode(token, &self.keys, Utc::now().timestamp()) else {
+            return Ok(None);
+        };
+        let current = sqlx::query!(
+            r#"SELECT session_generation,
+            EXISTS(SELECT 1 FROM revoked_sessions WHERE id = $2) AS "revoked!"
+            FROM users WHERE id = $1;"#,
+            claims.user_id,
+            claims.session_id,
+        )
+        .fetch_optional(&self.db)
+        .await?;
+        match current {
+            Some(current)
+                if current.session_generation == claims.generation && !current.revoked =>
+            {
+                Ok(Some(claims.as_session()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Signed sessions are not recorded anywhere, so there is nothing to list
+    async fn list_active(&self, _user_id: Uuid) -> Result<Vec<Session>, sqlx::Error> {
+        Ok(Vec::new())
+    }
+
+    /// The cookie stays valid as far as its signature goes, so its id is
+    /// remembered for as long as a session can last
+    async fn delete(&self, session_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO revoked_sessions (id, user_id, expires_at) VALUES($1,$2,$3)
+            ON CONFLICT (id) DO NOTHING;",
+            session_id,
+            user_id,
+            Utc::now() + Duration::days(SESSION_LIFETIME_DAYS),
+        )
+        .execute(&self.db)
+        .await
+        .map(|result| result.rows_affected() > 0)
+    }
+
+    async fn delete_all(&self, user_id: Uuid) -> Result<u64, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE users SET session_generation = session_generation + 1 WHERE id = $1;",
+            user_id
+        )
+        .execute(&self.db)
+        .await
+        .map(|result| result.rows_affected())
+    }
+
+    async fn delete_all_in(
+        &self,
+        user_id: Uuid,
+        conn: &mut PgConnection,
+    ) -> Result<u64, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE users SET session_generation = session_generation + 1 WHERE id = $1;",
+            user_id
+        )
+        .execute(conn)
+        .await
+        .map(|result| result.rows_affected())
+    }
+
+    /// Only revoked ids are stored, they can go once the session would have expired
+    async fn delete_expired(&self) -> Result<u64, sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM revoked_sessions WHERE expires_at <= $1;",
+            Utc::now()
+        )
+        .execute(&self.db)
+        .await
+        .map(|result| result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(expires_at: i64) -> Claims {
+        Claims {
+            session_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            generation: 3,
+            created_at: 1_000,
+            expires_at,
+        }
+    }
+
+    fn key(byte: u8) -> SessionKey {
+        SessionKey(vec![byte; 32])
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let claims = claims(2_000);
+        let token = encode(&claims, &key(1));
+        assert_eq!(decode(&token, &[key(1)], 1_500), Some(claims));
+    }
+
+    #[test]
+    fn test_decode_accepts_rotated_keys() {
+        let claims = claims(2_000);
+        let token = encode(&claims, &key(1));
+        assert_eq!(decode(&token, &[key(2), key(1)], 1_500), Some(claims));
+        assert_eq!(decode(&token, &[key(2)], 1_500), None);
+    }
+
+    #[test]
+    fn test_decode_rejects_tampered_and_expired() {
+        let claims = claims(2_000);
+        let token = encode(&claims, &key(1));
+        let tampered = token.replacen(".3.", ".4.", 1);
+        assert_ne!(tampered, token);
+        assert_eq!(decode(&tampered, &[key(1)], 1_500), None);
+        assert_eq!(decode(&token, &[key(1)], 2_000), None);
+        assert_eq!(decode("garbage", &[key(1)], 1_500), None);
+    }
+}
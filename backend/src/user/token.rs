@@ -0,0 +1,158 @@
+use std::fmt;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use super::model::{ApiToken, User};
+
+/// Prefix of every token, makes leaked tokens easy to recognize
+const TOKEN_PREFIX: &str = "rtr_";
+
+/// Permission granted to an API token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "read")]
+    Read,
+    #[serde(rename = "posts:write")]
+    PostsWrite,
+    #[serde(rename = "threads:write")]
+    ThreadsWrite,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::PostsWrite => "posts:write",
+            Scope::ThreadsWrite => "threads:write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        match scope {
+            "read" => Some(Scope::Read),
+            "posts:write" => Some(Scope::PostsWrite),
+            "threads:write" => Some(Scope::ThreadsWrite),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Generates a new random token, only ever shown to the user once
+pub fn generate() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let random: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("{TOKEN_PREFIX}{random}")
+}
+
+/// Tokens are random, so a plain SHA-256 is enough to store them
+pub fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub async fn create(
+    user_id: Uuid,
+    name: &str,
+    scopes: &[Scope],
+    token: &str,
+    db: &Pool<Postgres>,
+) -> Result<ApiToken, sqlx::Error> {
+    let scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+    sqlx::query_as!(
+        ApiToken,
+        "INSERT INTO api_tokens (id, user_id, name, token_hash, scopes, created_at)
+        VALUES($1,$2,$3,$4,$5,$6) RETURNING *;",
+        Uuid::new_v4(),
+        user_id,
+        name,
+        hash(token),
+        &scopes,
+        Utc::now(),
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// Resolves a presented token to its owner and the token itself, recording its use
+pub async fn find_user(
+    token: &str,
+    db: &Pool<Postgres>,
+) -> Result<Option<(ApiToken, User)>, sqlx::Error> {
+    let Some(api_token) = sqlx::query_as!(
+        ApiToken,
+        "UPDATE api_tokens SET last_used_at = $1 WHERE token_hash = $2 RETURNING *;",
+        Utc::now(),
+        hash(token),
+    )
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let user = sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE id = $1;",
+        api_token.user_id
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(Some((api_token, user)))
+}
+
+pub async fn list(user_id: Uuid, db: &Pool<Postgres>) -> Result<Vec<ApiToken>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiToken,
+        "SELECT * FROM api_tokens WHERE user_id = $1 ORDER BY created_at DESC;",
+        user_id
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Deletes a token of the user, returns whether anything was deleted
+pub async fn delete(
+    token_id: Uuid,
+    user_id: Uuid,
+    db: &Pool<Postgres>,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM api_tokens WHERE id = $1 AND user_id = $2;",
+        token_id,
+        user_id
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate();
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_eq!(token.len(), TOKEN_PREFIX.len() + 64);
+        assert_ne!(token, generate());
+    }
+
+    #[test]
+    fn test_scope_round_trip() {
+        for scope in [Scope::Read, Scope::PostsWrite, Scope::ThreadsWrite] {
+            assert_eq!(Scope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(Scope::parse("admin"), None);
+    }
+}
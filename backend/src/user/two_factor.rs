@@ -0,0 +1,295 @@
+use actix_web::{
+    cookie::{time, Cookie, SameSite},
+    HttpRequest,
+};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, Pool, Postgres};
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use super::error::Error;
+
+const ISSUER: &str = "Retoro";
+/// Number of recovery codes handed out at once, for 2FA and for account recovery
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Characters of a recovery code, without the easily confused 0/O and 1/I
+const RECOVERY_CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+/// How long the password step of a login stays valid while waiting for the second factor
+const PENDING_LOGIN_MINUTES: i64 = 5;
+/// Cookie carrying the pending login of a single sign-on, which cannot hand
+/// the token to the frontend in a response body
+pub const PENDING_LOGIN_COOKIE: &str = "two_factor_token";
+const PENDING_LOGIN_COOKIE_PATH: &str = "/api/users/login/2fa";
+/// Codes tried for a single pending login before it is dropped
+const PENDING_LOGIN_MAX_ATTEMPTS: i32 = 5;
+/// Seconds each TOTP code is valid for
+const TOTP_STEP_SECONDS: i64 = 30;
+
+/// Generates a new base32 encoded TOTP secret
+pub fn new_secret() -> String {
+    match Secret::generate_secret().to_encoded() {
+        Secret::Encoded(secret) => secret,
+        Secret::Raw(_) => unreachable!("to_encoded always returns Secret::Encoded"),
+    }
+}
+
+fn totp(secret: &str, username: &str) -> Result<TOTP, Error> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|_| Error::InvalidTwoFactorCode)?;
+    // `:` separates issuer and account in the provisioning URI
+    Ok(TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        1,
+        TOTP_STEP_SECONDS as u64,
+        secret,
+        Some(ISSUER.to_string()),
+        username.replace(':', "_"),
+    ))
+}
+
+/// `otpauth://` URI of the secret, meant to be rendered as a QR code by the client
+pub fn provisioning_uri(secret: &str, username: &str) -> Result<String, Error> {
+    Ok(totp(secret, username)?.get_url())
+}
+
+/// Checks a code from the authenticator, allowing one step of clock skew, and
+/// returns the time step it belongs to so it can be marked as used
+pub fn verify_code(secret: &str, username: &str, code: &str) -> Result<i64, Error> {
+    let now = Utc::now().timestamp();
+    verify_code_at(secret, username, code, now)
+}
+
+fn verify_code_at(secret: &str, username: &str, code: &str, now: i64) -> Result<i64, Error> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let totp = totp(secret, username)?;
+    let current = now / TOTP_STEP_SECONDS;
+    (current - 1..=current + 1)
+        .find(|step| codes_match(&totp.generate((step * TOTP_STEP_SECONDS) as u64), &code))
+        .ok_or(Error::InvalidTwoFactorCode)
+}
+
+/// Compares without returning early, so the time taken does not reveal the matching prefix
+fn codes_match(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .bytes()
+            .zip(actual.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Marks the time step of a code as used, returns false if it or a later one was used already
+pub async fn use_time_step(
+    user_id: Uuid,
+    step: i64,
+    db: &Pool<Postgres>,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "UPDATE users SET totp_last_step = $2
+        WHERE id = $1 AND (totp_last_step IS NULL OR totp_last_step < $2);",
+        user_id,
+        step,
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Generates a fresh set of recovery codes formatted as `XXXXX-XXXXX`
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 10];
+            OsRng.fill_bytes(&mut bytes);
+            let chars: String = bytes
+                .iter()
+                .map(|byte| RECOVERY_CODE_ALPHABET[(*byte & 31) as usize] as char)
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Recovery codes are random, so a plain SHA-256 is enough to store them
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+/// Replaces all recovery codes of the user with the given ones
+pub async fn store_recovery_codes(
+    user_id: Uuid,
+    codes: &[String],
+    conn: &mut PgConnection,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM two_factor_recovery_codes WHERE user_id = $1;",
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    for code in codes {
+        sqlx::query!(
+            "INSERT INTO two_factor_recovery_codes (id, user_id, code_hash) VALUES($1,$2,$3);",
+            Uuid::new_v4(),
+            user_id,
+            hash_recovery_code(code),
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Marks a recovery code as used, returns whether it was valid and unused
+pub async fn use_recovery_code(
+    user_id: Uuid,
+    code: &str,
+    db: &Pool<Postgres>,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "UPDATE two_factor_recovery_codes SET used_at = $1
+        WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL;",
+        Utc::now(),
+        user_id,
+        hash_recovery_code(code),
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Stores a login which passed the password check, its id is the token for the second step
+pub async fn create_pending_login(user_id: Uuid, db: &Pool<Postgres>) -> Result<Uuid, sqlx::Error> {
+    let now = Utc::now();
+    sqlx::query_scalar!(
+        "INSERT INTO pending_logins (id, user_id, created_at, expires_at)
+        VALUES($1,$2,$3,$4) RETURNING id;",
+        Uuid::new_v4(),
+        user_id,
+        now,
+        now + Duration::minutes(PENDING_LOGIN_MINUTES),
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// HttpOnly so scripts cannot read it, and unlike a URL parameter it does not
+/// end up in the history, logs or `Referer` headers
+pub fn pending_login_cookie(token: Uuid) -> Cookie<'static> {
+    Cookie::build(PENDING_LOGIN_COOKIE, token.to_string())
+        .max_age(time::Duration::minutes(PENDING_LOGIN_MINUTES))
+        .path(PENDING_LOGIN_COOKIE_PATH)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .finish()
+}
+
+/// Cookie which makes the browser drop its `two_factor_token`
+pub fn pending_login_removal_cookie() -> Cookie<'static> {
+    let mut cookie = Cookie::build(PENDING_LOGIN_COOKIE, "")
+        .path(PENDING_LOGIN_COOKIE_PATH)
+        .finish();
+    cookie.make_removal();
+    cookie
+}
+
+pub fn pending_login_from_request(req: &HttpRequest) -> Option<Uuid> {
+    req.cookie(PENDING_LOGIN_COOKIE)
+        .and_then(|cookie| Uuid::parse_str(cookie.value()).ok())
+}
+
+/// Uses up one attempt of a pending login and returns the user waiting for the
+/// second factor, `None` once the token expired or ran out of attempts. The
+/// attempt is claimed before the code is checked, so parallel requests cannot
+/// get past the limit.
+pub async fn claim_attempt(token: Uuid, db: &Pool<Postgres>) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "UPDATE pending_logins SET attempts = attempts + 1
+        WHERE id = $1 AND expires_at > $2 AND attempts < $3
+        RETURNING user_id;",
+        token,
+        Utc::now(),
+        PENDING_LOGIN_MAX_ATTEMPTS,
+    )
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn delete_pending_login(token: Uuid, db: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM pending_logins WHERE id = $1;", token)
+        .execute(db)
+        .await
+        .map(|_| ())
+}
+
+pub async fn delete_expired_pending_logins(db: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM pending_logins WHERE expires_at <= $1;",
+        Utc::now()
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_current_code() {
+        let secret = new_secret();
+        let code = totp(&secret, "alice").unwrap().generate_current().unwrap();
+        assert!(verify_code(&secret, "alice", &code).is_ok());
+    }
+
+    #[test]
+    fn test_verify_returns_time_step_with_skew() {
+        let secret = new_secret();
+        let now = 1_700_000_015;
+        let totp = totp(&secret, "alice").unwrap();
+        let step = now / TOTP_STEP_SECONDS;
+        for offset in -1..=1 {
+            let code = totp.generate(((step + offset) * TOTP_STEP_SECONDS) as u64);
+            assert_eq!(
+                verify_code_at(&secret, "alice", &code, now).unwrap(),
+                step + offset
+            );
+        }
+        let code = totp.generate(((step + 2) * TOTP_STEP_SECONDS) as u64);
+        assert!(verify_code_at(&secret, "alice", &code, now).is_err());
+    }
+
+    #[test]
+    fn test_verify_wrong_code() {
+        let secret = new_secret();
+        let code = totp(&secret, "alice").unwrap().generate(0);
+        assert!(verify_code(&secret, "alice", &code).is_err());
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let uri = provisioning_uri("JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP", "ali:ce").unwrap();
+        assert!(uri.starts_with("otpauth://totp/Retoro:ali_ce?"));
+        assert!(uri.contains("secret=JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP"));
+    }
+
+    #[test]
+    fn test_recovery_code_hash_ignores_formatting() {
+        let code = &generate_recovery_codes()[0];
+        assert_eq!(code.len(), 11);
+        assert_eq!(
+            hash_recovery_code(code),
+            hash_recovery_code(&code.replace('-', "").to_lowercase())
+        );
+    }
+}
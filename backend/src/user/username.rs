@@ -0,0 +1,195 @@
+use log::{error, info, warn};
+use sqlx::{Pool, Postgres};
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::{confusable_detection::skeleton, GeneralSecurityProfile, MixedScript};
+use uuid::Uuid;
+
+use super::error::Error;
+
+const MIN_LENGTH: usize = 3;
+const MAX_LENGTH: usize = 32;
+/// Separators allowed between the letters and digits of a name
+const SEPARATORS: [char; 4] = [' ', '_', '-', '.'];
+/// Names nobody can register, compared by their key so look-alikes are caught too
+const RESERVED: [&str; 9] = [
+    "admin",
+    "administrator",
+    "anonymous",
+    "moderator",
+    "mod",
+    "root",
+    "staff",
+    "system",
+    "retoro",
+];
+
+/// Normalizes a requested username and checks it against the naming rules
+pub fn normalize(raw: &str) -> Result<String, Error> {
+    let name: String = raw.trim().nfkc().collect();
+
+    let length = name.chars().count();
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+        return Err(Error::InvalidUsername(format!(
+            "Username must be between {MIN_LENGTH} and {MAX_LENGTH} characters long"
+        )));
+    }
+    if !name
+        .chars()
+        .all(|c| SEPARATORS.contains(&c) || (c.is_alphanumeric() && c.identifier_allowed()))
+    {
+        return Err(Error::InvalidUsername(format!(
+            "Username may only contain letters, digits and \"{}\"",
+            SEPARATORS.iter().collect::<String>()
+        )));
+    }
+    if !name.starts_with(char::is_alphanumeric) || !name.ends_with(char::is_alphanumeric) {
+        return Err(Error::InvalidUsername(
+            "Username must start and end with a letter or digit".to_string(),
+        ));
+    }
+    if name
+        .chars()
+        .zip(name.chars().skip(1))
+        .any(|(a, b)| SEPARATORS.contains(&a) && SEPARATORS.contains(&b))
+    {
+        return Err(Error::InvalidUsername(
+            "Username may not contain consecutive separators".to_string(),
+        ));
+    }
+    if !name.as_str().is_single_script() {
+        return Err(Error::InvalidUsername(
+            "Username may not mix letters of different scripts".to_string(),
+        ));
+    }
+    if RESERVED.iter().any(|reserved| key(reserved) == key(&name)) {
+        return Err(Error::InvalidUsername(
+            "This username is reserved".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+/// Key under which names are unique: case-folded confusable skeleton, so
+/// "Alice", "alice" and "Аlice" (Cyrillic А) all map to the same key.
+/// Folded again after the skeleton since confusables may map to uppercase letters.
+pub fn key(name: &str) -> String {
+    let folded: String = name.trim().nfkc().flat_map(char::to_lowercase).collect();
+    skeleton(&folded).flat_map(char::to_lowercase).collect()
+}
+
+/// Fills `name_key` of users registered before it existed. Users are visited oldest
+/// first, so the oldest of colliding names keeps the plain key and later ones get a
+/// suffixed key ("alice#2"), which no valid name can produce
+pub async fn backfill_keys(db: &Pool<Postgres>) {
+    let users: Vec<(Uuid, String)> = match sqlx::query_as(
+        "SELECT id, name FROM users WHERE name_key IS NULL ORDER BY created_at, id;",
+    )
+    .fetch_all(db)
+    .await
+    {
+        Ok(users) => users,
+        Err(err) => {
+            error!("Loading users without name key failed: {err}");
+            return;
+        }
+    };
+    if users.is_empty() {
+        return;
+    }
+    let mut filled = 0;
+    for (id, name) in users {
+        match fill_key(id, &name, db).await {
+            Ok(name_key) => {
+                if name_key != key(&name) {
+                    warn!("User {name} ({id}) collides with another name, keyed as {name_key}");
+                }
+                filled += 1;
+            }
+            Err(err) => error!("Filling name key of user {name} ({id}) failed: {err}"),
+        }
+    }
+    info!("Filled name keys of {filled} users");
+}
+
+/// Stores the first free key out of the plain key and its suffixed variants
+async fn fill_key(id: Uuid, name: &str, db: &Pool<Postgres>) -> Result<String, sqlx::Error> {
+    let base = key(name);
+    for n in 1.. {
+        let name_key = suffixed_key(&base, n);
+        match sqlx::query!(
+            "UPDATE users SET name_key = $1 WHERE id = $2;",
+            name_key,
+            id
+        )
+        .execute(db)
+        .await
+        {
+            Ok(_) => return Ok(name_key),
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!()
+}
+
+fn suffixed_key(base: &str, n: u32) -> String {
+    match n {
+        1 => base.to_string(),
+        n => format!("{base}#{n}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_trims_and_applies_nfkc() {
+        assert_eq!(normalize("  Alice ").unwrap(), "Alice");
+        assert_eq!(normalize("ｂｏｂ").unwrap(), "bob");
+    }
+
+    #[test]
+    fn test_normalize_rejects_length() {
+        assert!(normalize("ab").is_err());
+        assert!(normalize(&"a".repeat(33)).is_err());
+    }
+
+    #[test]
+    fn test_normalize_rejects_charset() {
+        assert!(normalize("ali\u{7}ce").is_err());
+        assert!(normalize("ali%ce").is_err());
+        assert!(normalize("alice\u{200b}").is_err());
+        assert!(normalize("_alice").is_err());
+        assert!(normalize("al  ice").is_err());
+        assert!(normalize("Alice Johnson").is_ok());
+    }
+
+    #[test]
+    fn test_normalize_rejects_mixed_script() {
+        assert!(normalize("\u{410}lice").is_err());
+        assert!(normalize("Алиса").is_ok());
+    }
+
+    #[test]
+    fn test_normalize_rejects_reserved() {
+        assert!(normalize("Admin").is_err());
+        assert!(normalize("anonymous").is_err());
+        assert!(normalize("M0derator").is_err());
+    }
+
+    #[test]
+    fn test_key_matches_case_and_confusables() {
+        assert_eq!(key("Alice"), key("alice"));
+        assert_eq!(key("alice"), key("\u{430}lice"));
+        assert_eq!(key("b0b"), key("BOB"));
+        assert_ne!(key("alice"), key("alicia"));
+    }
+
+    #[test]
+    fn test_suffixed_key_is_never_a_valid_name() {
+        assert_eq!(suffixed_key("alice", 1), "alice");
+        assert_eq!(suffixed_key("alice", 2), "alice#2");
+        assert!(normalize("alice#2").is_err());
+    }
+}
@@ -2,15 +2,16 @@ import { ChangeEvent, useState } from "react";
 import Markdown from "react-markdown";
 
 interface PostInputDialogProps {
-  onSubmit: (content: string) => void;
+  onSubmit: (content: string, sage: boolean) => void;
 }
 
 export function PostInputDialog({ onSubmit }: PostInputDialogProps) {
   const [content, setContent] = useState("");
   const [checked, setChecked] = useState(false);
+  const [sage, setSage] = useState(false);
 
   const handleSubmit = async () => {
-    await onSubmit(content);
+    await onSubmit(content, sage);
   };
 
   return (
@@ -37,6 +38,15 @@ export function PostInputDialog({ onSubmit }: PostInputDialogProps) {
           />
           Preview
         </label>
+        <label class="switch">
+          <input
+            class="standard-checkbox"
+            type="checkbox"
+            checked={sage}
+            onInput={() => setSage(!sage)}
+          />
+          Sage
+        </label>
         <button onClick={handleSubmit} class="big standard-button float-right">
           Post
         </button>
@@ -5,12 +5,29 @@ interface PostProps {
 }
 export function PostView(props: PostProps) {
   const formatedDate = new Date(props.post.created_at).toLocaleString();
+  if (props.post.deleted_at) {
+    return (
+      <div id={props.post.id} class="post deleted">
+        <h6 class="datetime">{formatedDate}</h6>
+        <p>
+          Post deleted
+          {props.post.delete_reason && `: ${props.post.delete_reason}`}
+        </p>
+      </div>
+    );
+  }
   return (
     <div id={props.post.id} class="post">
       <div class="post-header">
         <h4 class="author">{`${
           props.post.author_id == null ? "Anonymous" : props.post.author_name
         }`}</h4>
+        {props.post.tripcode && (
+          <h6 class="tripcode">{props.post.tripcode}</h6>
+        )}
+        {props.post.poster_id && (
+          <h6 class="poster-id">ID: {props.post.poster_id}</h6>
+        )}
         <h6 class="datetime">{formatedDate}</h6>
       </div>
       <hr class="secondary" />
@@ -28,4 +45,8 @@ export interface Post {
   created_at: Date;
   id: string;
   thread_id: string;
+  poster_id?: string;
+  tripcode?: string;
+  deleted_at?: Date;
+  delete_reason?: string;
 }
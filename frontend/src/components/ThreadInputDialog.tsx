@@ -2,8 +2,10 @@ import axios from "axios";
 import { ChangeEvent, useState } from "react";
 import Markdown from "react-markdown";
 import { Thread } from "./ThreadSelctor";
+import { User } from "./UserPanel";
 
 interface ThreadInputDialogProps {
+  user?: User;
   toggleHidden: () => void;
   hidden: boolean;
   refreshThreads: () => void;
@@ -12,6 +14,9 @@ interface ThreadInputDialogProps {
 
 export function ThreadInputDialog(props: ThreadInputDialogProps) {
   const [newThread, setNewThread] = useState(null);
+  const [description, setDescription] = useState("");
+  const [tags, setTags] = useState("");
+  const [openingPost, setOpeningPost] = useState("");
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState(null);
 
@@ -21,10 +26,19 @@ export function ThreadInputDialog(props: ThreadInputDialogProps) {
   const addThread = async () => {
     try {
       setLoading(true); // Start loading
-      if (newThread != "" && newThread != null) {
-        const response = await axios.post("http://localhost:8080/api/threads", {
-          name: newThread,
-        });
+      if (newThread != "" && newThread != null && openingPost != "") {
+        // The thread and its opening post are created together
+        const response = await axios.post(
+          "http://localhost:8080/api/threads",
+          {
+            name: newThread,
+            description: description.trim() || undefined,
+            author_id: props.user ? props.user.id : null,
+            content: openingPost,
+            tags: tags.split(/[\s,]+/).filter((tag) => tag != ""),
+          },
+          { withCredentials: true }
+        );
         console.log(response.data as Thread);
 
         console.log(response);
@@ -49,6 +63,26 @@ export function ThreadInputDialog(props: ThreadInputDialogProps) {
           value={newThread}
           onInput={(v) => handleNewThread(v.currentTarget.value)}
         />
+        <input
+          class="standard-input"
+          type="text"
+          value={description}
+          placeholder={"Description (optional)"}
+          onInput={(v) => setDescription(v.currentTarget.value)}
+        />
+        <input
+          class="standard-input"
+          type="text"
+          value={tags}
+          placeholder={"Tags, separated by spaces (optional)"}
+          onInput={(v) => setTags(v.currentTarget.value)}
+        />
+        <textarea
+          class="post-input-area"
+          value={openingPost}
+          placeholder={"Opening post..."}
+          onInput={(v) => setOpeningPost(v.currentTarget.value)}
+        />
         <div>
           <button class="standard-button big" onClick={addThread}>
             Add thread
@@ -2,8 +2,10 @@ import axios from "axios";
 import { useEffect, useState } from "preact/hooks";
 import { Thread, ThreadSelector } from "./ThreadSelctor";
 import { ThreadInputDialog } from "./ThreadInputDialog";
+import { User } from "./UserPanel";
 
 interface ThreadListProps {
+  user?: User;
   onSelectThread: (thread: Thread) => void;
   selectedThread: string | null;
 }
@@ -20,7 +22,9 @@ export const ThreadList = (props: ThreadListProps) => {
   const fetchThreads = async () => {
     try {
       setLoading(true); // Start loading
-      const response = await axios.get("http://localhost:8080/api/threads");
+      const response = await axios.get(
+        "http://localhost:8080/api/threads?sort=bump"
+      );
       if (response.data != threads) {
         setThreads(response.data); // Save the data
       }
@@ -64,6 +68,7 @@ export const ThreadList = (props: ThreadListProps) => {
         );
       })}
       <ThreadInputDialog
+        user={props.user}
         selectThread={props.onSelectThread}
         refreshThreads={() => setThreadsRefreshKey((prev) => prev + 1)}
         hidden={addThreadHidden}
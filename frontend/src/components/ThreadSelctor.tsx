@@ -1,3 +1,7 @@
+// Pinned threads are listed first anyway, the markers tell why
+const threadTitle = (thread: Thread) =>
+  (thread.pinned ? "📌 " : "") + (thread.locked ? "🔒 " : "") + thread.name;
+
 interface ThreadSelectorProps {
   thread: Thread;
   selected: boolean;
@@ -8,17 +12,19 @@ export const ThreadSelector = (props: ThreadSelectorProps) => {
     <div
       id={props.thread.id}
       class="thread selected"
+      title={props.thread.preview}
       onClick={() => props.onSelectThread(props.thread)}
     >
-      <h4>{props.thread.name}</h4>
+      <h4>{threadTitle(props.thread)}</h4>
     </div>
   ) : (
     <div
       id={props.thread.id}
       class="thread"
+      title={props.thread.preview}
       onClick={() => props.onSelectThread(props.thread)}
     >
-      <h4>{props.thread.name}</h4>
+      <h4>{threadTitle(props.thread)}</h4>
     </div>
   );
 };
@@ -28,4 +34,18 @@ export interface Thread {
   name: string;
   created_at: Date;
   last_active: Date;
+  author_id?: string;
+  description?: string;
+  pinned: boolean;
+  locked: boolean;
+  archived_at?: Date;
+  board_id?: string;
+  tags: string[];
+  post_count: number;
+  participant_count: number;
+  last_post_at?: Date;
+  last_poster?: string;
+  preview?: string;
+  deleted_at?: Date;
+  delete_reason?: string;
 }
@@ -48,6 +48,11 @@ export function UserLoginDialog(props: UserLoginDialogProps) {
         >
           Login
         </button>
+        <br />
+        {/* Only works when the backend has OIDC configured */}
+        <a class="standard-button" href="http://localhost:8080/api/users/oidc/login">
+          Log in with single sign-on
+        </a>
       </div>
     </div>
   );
@@ -11,7 +11,16 @@ interface UserOptionsDialogProps {
 }
 
 export function UserOptionsDialog(props: UserOptionsDialogProps) {
-  function logout() {
+  async function logout() {
+    try {
+      await axios.post(
+        "http://localhost:8080/api/users/logout",
+        {},
+        { withCredentials: true }
+      );
+    } catch (error) {
+      console.error("Logout failed:", error);
+    }
     document.cookie =
       "session_id=; expires=Thu, 01 Jan 1970 00:00:00 UTC; path=/;";
 
@@ -57,7 +57,11 @@ export const UserPanel = (props: UserPanelProps) => {
     }
   };
 
-  const onRegister = async (username: string, password: string) => {
+  const onRegister = async (
+    username: string,
+    password: string,
+    inviteCode: string
+  ) => {
     try {
       setLoading(true); // Start loading
 
@@ -66,6 +70,7 @@ export const UserPanel = (props: UserPanelProps) => {
         {
           name: username,
           password: password,
+          invite_code: inviteCode.trim() || undefined,
         },
         {
           withCredentials: true, // Ensures the browser stores the session_id cookie
@@ -78,6 +83,11 @@ export const UserPanel = (props: UserPanelProps) => {
       props.handleUser(response.data as User); // Set the logged-in user
       console.debug(response.data); // Log the response data
       setRegisterHidden(true);
+      // Recovery codes are only returned once, there is no other way to reset a password
+      alert(
+        "Save these recovery codes, each one can reset your password once:\n\n" +
+          response.data.recovery_codes.join("\n")
+      );
     } catch (err) {
       setError(err.message); // Save the error
       console.error("Login error:", err); // Log the error for debugging
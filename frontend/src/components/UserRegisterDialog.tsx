@@ -4,7 +4,7 @@ import Markdown from "react-markdown";
 import { User } from "./UserPanel";
 
 interface UserRegisterDialogProps {
-  onRegister: (username: string, password: string) => void;
+  onRegister: (username: string, password: string, inviteCode: string) => void;
   hidden: boolean;
 }
 
@@ -12,6 +12,7 @@ export function UserRegisterDialog(props: UserRegisterDialogProps) {
   const [username, setUsername] = useState("");
   const [password, setPassword] = useState("");
   const [passwordRepeat, setPasswordRepeat] = useState("");
+  const [inviteCode, setInviteCode] = useState("");
 
   return (
     <div hidden={props.hidden}>
@@ -52,10 +53,22 @@ export function UserRegisterDialog(props: UserRegisterDialogProps) {
             placeholder={"Repeat your very secure password..."}
           />
         </label>
+        <br />
+        <label class="login">
+          <input
+            class="standard-input"
+            type="text"
+            value={inviteCode}
+            onInput={(event) => {
+              setInviteCode(event.currentTarget.value);
+            }}
+            placeholder={"Invite code, if you got one..."}
+          />
+        </label>
         <button
           onClick={() => {
             if (password == passwordRepeat)
-              props.onRegister(username, password);
+              props.onRegister(username, password, inviteCode);
             else {
               alert("Passwords don't match");
             }
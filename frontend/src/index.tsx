@@ -1,9 +1,21 @@
 import { render } from "preact";
+import axios from "axios";
 import { LocationProvider, Router, Route } from "preact-iso";
 
 import "./style.css";
 import { Home } from "./pages/Home";
 
+// The backend rejects state-changing requests of a session which do not repeat its CSRF cookie
+axios.interceptors.request.use((config) => {
+  const csrfCookie = document.cookie
+    .split("; ")
+    .find((cookie) => cookie.startsWith("csrf_token="));
+  if (csrfCookie) {
+    config.headers["X-CSRF-Token"] = csrfCookie.split("=")[1];
+  }
+  return config;
+});
+
 export function App() {
   return (
     <div class="app">
@@ -53,6 +53,70 @@ export function Home() {
     }
   };
 
+  // The backend sends the browser back here after a single sign-on login
+  const finishOidcLogin = async () => {
+    const params = new URLSearchParams(window.location.search);
+    const error = params.get("oidc_error");
+    // The signup itself travels in an HttpOnly cookie, this only says there is one
+    const signup = params.get("oidc_signup");
+    const twoFactor = params.get("two_factor");
+    if (!error && !signup && !twoFactor) {
+      return;
+    }
+    window.history.replaceState(null, "", window.location.pathname);
+    if (error) {
+      alert("Single sign-on failed: " + error);
+      return;
+    }
+    if (twoFactor) {
+      // The pending login travels in an HttpOnly cookie, only the code is sent
+      const code = prompt("Enter the code from your authenticator:");
+      if (!code) {
+        return;
+      }
+      try {
+        const response = await axios.post(
+          "http://localhost:8080/api/users/login/2fa",
+          { code: code },
+          {
+            withCredentials: true,
+            headers: {
+              "Content-Type": "application/json",
+            },
+          }
+        );
+        setUser(response.data as User);
+      } catch (error) {
+        alert("Login failed: " + (error.response?.data ?? error.message));
+      }
+      return;
+    }
+
+    const name = prompt("Pick a username:", params.get("name") ?? "");
+    if (!name) {
+      return;
+    }
+    try {
+      const response = await axios.post(
+        "http://localhost:8080/api/users/oidc/signup",
+        { name: name },
+        {
+          withCredentials: true,
+          headers: {
+            "Content-Type": "application/json",
+          },
+        }
+      );
+      setUser(response.data as User);
+      alert(
+        "Save these recovery codes, each one can reset your password once:\n\n" +
+          response.data.recovery_codes.join("\n")
+      );
+    } catch (error) {
+      alert("Sign-up failed: " + (error.response?.data ?? error.message));
+    }
+  };
+
   function initializeTheme() {
     const savedTheme = localStorage.getItem("theme");
     const prefersDark = window.matchMedia(
@@ -65,6 +129,7 @@ export function Home() {
 
   useEffect(() => {
     checkSession();
+    finishOidcLogin();
     initializeTheme();
     const interval = setInterval(() => {
       setRefreshKey((prev) => prev + 1);
@@ -81,11 +146,12 @@ export function Home() {
     setUser(user);
   };
 
-  const onSubmit = (post: string) => {
+  const onSubmit = (post: string, sage: boolean) => {
     const data = {
       thread_id: selectedThread.id,
       author_id: user ? user.id : null,
       content: post,
+      sage: sage,
     };
 
     axios
@@ -94,6 +160,7 @@ export function Home() {
           Accept: "application/json",
           "Content-Type": "application/json",
         },
+        withCredentials: true,
       })
       .then((response) => {
         console.log("Success:", response.data);
@@ -113,11 +180,16 @@ export function Home() {
         </div>
         {selectedThread ? (
           <ThreadList
+            user={user}
             selectedThread={selectedThread.id}
             onSelectThread={onSelectThread}
           />
         ) : (
-          <ThreadList selectedThread={null} onSelectThread={onSelectThread} />
+          <ThreadList
+            user={user}
+            selectedThread={null}
+            onSelectThread={onSelectThread}
+          />
         )}
         <UserPanel user={user} handleUser={handleUser} />
       </div>